@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS history
+(
+    video_id            TEXT PRIMARY KEY NOT NULL,
+    title               TEXT NOT NULL,
+    url                 TEXT NOT NULL,
+    channel_id          TEXT NOT NULL,
+    channel_username    TEXT NOT NULL,
+    started_at          TEXT NOT NULL,
+    last_position       REAL NOT NULL DEFAULT 0,
+    duration            REAL,
+    completed           BOOLEAN NOT NULL DEFAULT FALSE
+);
@@ -0,0 +1 @@
+ALTER TABLE feed ADD COLUMN watched BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS sponsorblock_cache
+(
+    video_id    TEXT PRIMARY KEY NOT NULL,
+    fetched_at  TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS sponsorblock_segments
+(
+    video_id    TEXT NOT NULL,
+    category    TEXT NOT NULL,
+    start_time  REAL NOT NULL,
+    end_time    REAL NOT NULL,
+    FOREIGN KEY (video_id) REFERENCES sponsorblock_cache(video_id) ON DELETE CASCADE
+);
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS failed_downloads
+(
+    url             TEXT NOT NULL,
+    download_type   TEXT NOT NULL,
+    title           TEXT NOT NULL,
+    format          TEXT,
+    error           TEXT NOT NULL,
+    failed_at       TEXT NOT NULL,
+    PRIMARY KEY (url, download_type)
+);
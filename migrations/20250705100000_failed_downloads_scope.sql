@@ -0,0 +1 @@
+ALTER TABLE failed_downloads ADD COLUMN scope TEXT NOT NULL DEFAULT 'single';
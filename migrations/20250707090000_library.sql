@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS library
+(
+    path                TEXT PRIMARY KEY NOT NULL,
+    video_id            TEXT NOT NULL,
+    title               TEXT NOT NULL,
+    channel_username    TEXT NOT NULL,
+    download_type       TEXT NOT NULL,
+    format              TEXT NOT NULL,
+    size                INTEGER NOT NULL,
+    downloaded_at       TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS library_video_id ON library (video_id);
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS auto_download_rules
+(
+    channel_id          TEXT PRIMARY KEY NOT NULL,
+    audio_only          BOOLEAN NOT NULL DEFAULT FALSE,
+    min_duration        INTEGER,
+    title_pattern       TEXT,
+    keep_last           INTEGER,
+    keep_days           INTEGER,
+    delete_watched      BOOLEAN NOT NULL DEFAULT FALSE,
+    FOREIGN KEY (channel_id) REFERENCES subscriptions(channel_id) ON DELETE CASCADE
+);
+
+ALTER TABLE failed_downloads ADD COLUMN match_filter TEXT;
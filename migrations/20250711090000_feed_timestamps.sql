@@ -0,0 +1,7 @@
+-- Timestamps were written with chrono's `to_string`, e.g. `2025-06-15 13:30:44.123 UTC`; store
+-- them as RFC 3339, which also sorts correctly as text
+UPDATE feed
+SET published_at = replace(replace(published_at, ' UTC', '+00:00'), ' ', 'T')
+WHERE published_at LIKE '% UTC';
+
+CREATE INDEX IF NOT EXISTS feed_published_at ON feed (published_at);
@@ -0,0 +1,22 @@
+-- Rebuild the feed so its rows go with their subscription, dropping those left behind by earlier
+-- unsubscribes
+CREATE TABLE feed_new
+(
+    id           TEXT PRIMARY KEY NOT NULL,
+    title        TEXT NOT NULL,
+    url          TEXT NOT NULL,
+    channel      TEXT NOT NULL,
+    published_at TEXT NOT NULL,
+    watched      BOOLEAN NOT NULL DEFAULT FALSE,
+    FOREIGN KEY (channel) REFERENCES subscriptions(channel_id) ON DELETE CASCADE
+);
+
+INSERT INTO feed_new ( id, title, url, channel, published_at, watched )
+SELECT id, title, url, channel, published_at, watched FROM feed
+WHERE channel IN (SELECT channel_id FROM subscriptions);
+
+DROP TABLE feed;
+ALTER TABLE feed_new RENAME TO feed;
+
+CREATE INDEX IF NOT EXISTS feed_published_at ON feed (published_at);
+CREATE INDEX IF NOT EXISTS feed_channel ON feed (channel);
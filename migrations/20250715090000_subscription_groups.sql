@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS subscription_groups
+(
+    id      INTEGER PRIMARY KEY NOT NULL,
+    name    TEXT NOT NULL UNIQUE COLLATE NOCASE
+);
+
+CREATE TABLE IF NOT EXISTS subscription_group_members
+(
+    group_id    INTEGER NOT NULL,
+    channel_id  TEXT NOT NULL,
+    PRIMARY KEY (group_id, channel_id),
+    FOREIGN KEY (group_id) REFERENCES subscription_groups(id) ON DELETE CASCADE,
+    FOREIGN KEY (channel_id) REFERENCES subscriptions(channel_id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS subscription_group_members_channel ON subscription_group_members (channel_id);
@@ -0,0 +1,3 @@
+-- Outcome of the last feed refresh of each subscription; `refresh_error` is NULL when it worked
+ALTER TABLE subscriptions ADD COLUMN refreshed_at TEXT;
+ALTER TABLE subscriptions ADD COLUMN refresh_error TEXT;
@@ -0,0 +1,99 @@
+ALTER TABLE feed ADD COLUMN description TEXT NOT NULL DEFAULT '';
+
+-- Offline search over the feed, history and library. `key` is the id of the row in the table
+-- named by `source`: the video id for the feed and history, the file path for the library
+CREATE VIRTUAL TABLE IF NOT EXISTS local_search USING fts5
+(
+    key UNINDEXED,
+    video_id UNINDEXED,
+    source UNINDEXED,
+    title,
+    channel,
+    description,
+    transcript,
+    tokenize = 'porter unicode61 remove_diacritics 2'
+);
+
+CREATE TRIGGER IF NOT EXISTS feed_search_insert AFTER INSERT ON feed
+BEGIN
+    INSERT INTO local_search ( key, video_id, source, title, channel, description, transcript )
+    VALUES (
+        NEW.id, NEW.id, 'feed', NEW.title,
+        (SELECT channel_username FROM subscriptions WHERE channel_id = NEW.channel),
+        NEW.description, ''
+    );
+END;
+
+CREATE TRIGGER IF NOT EXISTS feed_search_update AFTER UPDATE OF title, channel, description ON feed
+BEGIN
+    UPDATE local_search SET
+        title = NEW.title,
+        channel = (SELECT channel_username FROM subscriptions WHERE channel_id = NEW.channel),
+        description = NEW.description
+    WHERE source = 'feed' AND key = OLD.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS feed_search_delete AFTER DELETE ON feed
+BEGIN
+    DELETE FROM local_search WHERE source = 'feed' AND key = OLD.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS subscription_search_rename AFTER UPDATE OF channel_username ON subscriptions
+BEGIN
+    UPDATE local_search SET channel = NEW.channel_username
+    WHERE source = 'feed' AND key IN (SELECT id FROM feed WHERE channel = NEW.channel_id);
+END;
+
+CREATE TRIGGER IF NOT EXISTS history_search_insert AFTER INSERT ON history
+BEGIN
+    INSERT INTO local_search ( key, video_id, source, title, channel, description, transcript )
+    VALUES (
+        NEW.video_id, NEW.video_id, 'history', NEW.title, NEW.channel_username,
+        COALESCE((SELECT description FROM feed WHERE id = NEW.video_id), ''), ''
+    );
+END;
+
+CREATE TRIGGER IF NOT EXISTS history_search_update AFTER UPDATE OF title, channel_username ON history
+BEGIN
+    UPDATE local_search SET title = NEW.title, channel = NEW.channel_username
+    WHERE source = 'history' AND key = OLD.video_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS history_search_delete AFTER DELETE ON history
+BEGIN
+    DELETE FROM local_search WHERE source = 'history' AND key = OLD.video_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS library_search_insert AFTER INSERT ON library
+BEGIN
+    INSERT INTO local_search ( key, video_id, source, title, channel, description, transcript )
+    VALUES (
+        NEW.path, NEW.video_id, 'library', NEW.title, NEW.channel_username,
+        COALESCE((SELECT description FROM feed WHERE id = NEW.video_id), ''), ''
+    );
+END;
+
+CREATE TRIGGER IF NOT EXISTS library_search_update AFTER UPDATE OF video_id, title, channel_username ON library
+BEGIN
+    UPDATE local_search SET video_id = NEW.video_id, title = NEW.title, channel = NEW.channel_username
+    WHERE source = 'library' AND key = OLD.path;
+END;
+
+CREATE TRIGGER IF NOT EXISTS library_search_delete AFTER DELETE ON library
+BEGIN
+    DELETE FROM local_search WHERE source = 'library' AND key = OLD.path;
+END;
+
+INSERT INTO local_search ( key, video_id, source, title, channel, description, transcript )
+SELECT feed.id, feed.id, 'feed', feed.title, subscriptions.channel_username, feed.description, ''
+FROM feed JOIN subscriptions ON feed.channel = subscriptions.channel_id;
+
+INSERT INTO local_search ( key, video_id, source, title, channel, description, transcript )
+SELECT video_id, video_id, 'history', title, channel_username,
+    COALESCE((SELECT description FROM feed WHERE id = history.video_id), ''), ''
+FROM history;
+
+INSERT INTO local_search ( key, video_id, source, title, channel, description, transcript )
+SELECT path, video_id, 'library', title, channel_username,
+    COALESCE((SELECT description FROM feed WHERE id = library.video_id), ''), ''
+FROM library;
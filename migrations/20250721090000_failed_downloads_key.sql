@@ -0,0 +1,20 @@
+-- Clips and bulk scopes of a URL are failures of their own, apart from its full download
+CREATE TABLE failed_downloads_new
+(
+    url             TEXT NOT NULL,
+    download_type   TEXT NOT NULL,
+    scope           TEXT NOT NULL DEFAULT 'single',
+    title           TEXT NOT NULL,
+    channel         TEXT,
+    format          TEXT,
+    match_filter    TEXT,
+    error           TEXT NOT NULL,
+    failed_at       TEXT NOT NULL,
+    PRIMARY KEY (url, download_type, scope)
+);
+
+INSERT INTO failed_downloads_new ( url, download_type, scope, title, channel, format, match_filter, error, failed_at )
+SELECT url, download_type, scope, title, channel, format, match_filter, error, failed_at FROM failed_downloads;
+
+DROP TABLE failed_downloads;
+ALTER TABLE failed_downloads_new RENAME TO failed_downloads;
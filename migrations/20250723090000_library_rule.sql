@@ -0,0 +1,6 @@
+-- Channel of the auto-download rule a file was downloaded for, the only files retention deletes.
+-- Earlier downloads cannot be told apart from manual ones, so they are left alone.
+ALTER TABLE library ADD COLUMN rule_channel_id TEXT;
+ALTER TABLE failed_downloads ADD COLUMN rule_channel_id TEXT;
+
+CREATE INDEX IF NOT EXISTS library_rule_channel_id ON library (rule_channel_id);
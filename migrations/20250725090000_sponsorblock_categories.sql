@@ -0,0 +1,2 @@
+-- Categories the cached segments were fetched for; earlier entries have none and get refetched
+ALTER TABLE sponsorblock_cache ADD COLUMN categories TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,320 @@
+use std::path::PathBuf;
+
+pub mod settings;
+
+pub use settings::{Config, get};
+
+/// Directory for silk's own files, such as the download archive.
+pub fn data_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| expand_path("~/.local/share"))
+        .join("silk")
+}
+
+/// Expands a leading `~` and `$VAR` or `${VAR}` references, since paths are handed to programs
+/// without going through a shell. Unknown variables are left as they are.
+pub fn expand_path(path: &str) -> PathBuf {
+    let path = match (path.strip_prefix('~'), dirs::home_dir()) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{}{}", home.display(), rest)
+        }
+        _ => path.to_string(),
+    };
+
+    let mut expanded = String::with_capacity(path.len());
+    let mut rest = path.as_str();
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+
+        let (name, consumed) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            },
+            None => {
+                let end = after
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+
+        match std::env::var(name) {
+            Ok(value) if !name.is_empty() => expanded.push_str(&value),
+            _ => expanded.push_str(&rest[start..start + 1 + consumed]),
+        }
+        rest = &after[consumed..];
+    }
+    expanded.push_str(rest);
+
+    PathBuf::from(expanded)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PlaybackMode {
+    Video,
+    Audio,
+}
+
+impl PlaybackMode {
+    /// yt-dlp format selector used when no format was picked explicitly.
+    pub fn format(&self) -> String {
+        match self {
+            PlaybackMode::Video => get().player.format.clone(),
+            PlaybackMode::Audio => get().player.audio_format.clone(),
+        }
+    }
+}
+
+/// Which subtitle tracks are fetched: the ones uploaded with the video, YouTube's automatic
+/// captions, or both.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum SubtitleSource {
+    #[default]
+    Manual,
+    Auto,
+    Both,
+}
+
+impl SubtitleSource {
+    pub fn manual(&self) -> bool {
+        matches!(self, SubtitleSource::Manual | SubtitleSource::Both)
+    }
+
+    pub fn auto(&self) -> bool {
+        matches!(self, SubtitleSource::Auto | SubtitleSource::Both)
+    }
+}
+
+impl std::fmt::Display for SubtitleSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SubtitleSource::Manual => "manual",
+            SubtitleSource::Auto => "auto",
+            SubtitleSource::Both => "both",
+        })
+    }
+}
+
+impl std::str::FromStr for SubtitleSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "manual" => Ok(SubtitleSource::Manual),
+            "auto" => Ok(SubtitleSource::Auto),
+            "both" => Ok(SubtitleSource::Both),
+            other => Err(format!(
+                "Unknown subtitle source: {} (expected manual, auto or both)",
+                other
+            )),
+        }
+    }
+}
+
+pub mod env {
+    //! Environment variables and the `.env` file. `DATABASE_URL` picks the database, and the
+    //! `SILK_*` variables override the config file.
+
+    use std::path::PathBuf;
+
+    use super::{expand_path, settings::Config};
+
+    /// Loads the `.env` file of the working directory or its parents, when there is one, and
+    /// returns its path.
+    pub fn init() -> Result<Option<PathBuf>, String> {
+        match dotenvy::dotenv() {
+            Ok(path) => Ok(Some(path)),
+            Err(e) if e.not_found() => Ok(None),
+            Err(e) => Err(format!("Invalid .env file: {}", e)),
+        }
+    }
+
+    /// SQLite database, `silk.db` in the data directory unless `DATABASE_URL` is set.
+    pub fn database_url() -> String {
+        std::env::var("DATABASE_URL")
+            .unwrap_or_else(|_| format!("sqlite://{}", super::data_dir().join("silk.db").display()))
+    }
+
+    fn var(name: &str) -> Option<String> {
+        std::env::var(name).ok()
+    }
+
+    fn parse<T>(name: &str) -> Result<Option<T>, String>
+    where
+        T: std::str::FromStr,
+        T::Err: std::fmt::Display,
+    {
+        var(name)
+            .map(|value| value.parse().map_err(|e| format!("{}: {}", name, e)))
+            .transpose()
+    }
+
+    /// Overrides the settings of `config` with the `SILK_*` variables that are set.
+    pub fn apply(config: &mut Config) -> Result<(), String> {
+        let player = &mut config.player;
+        if let Some(name) = var("SILK_PLAYER") {
+            player.name = name;
+        }
+        if let Some(command) = var("SILK_PLAYER_COMMAND") {
+            player.command = Some(command);
+        }
+        if let Some(format) = var("SILK_FORMAT") {
+            player.format = format;
+        }
+        if let Some(langs) = var("SILK_SUBTITLE_LANGS") {
+            player.subtitle_langs = Some(langs).filter(|langs| !langs.trim().is_empty());
+        }
+        if let Some(source) = parse("SILK_SUBTITLE_SOURCE")? {
+            player.subtitle_source = source;
+        }
+
+        let downloads = &mut config.downloads;
+        if let Some(max) = parse("SILK_MAX_DOWNLOADS")? {
+            downloads.max_parallel = max;
+        }
+        if let Some(dir) = var("SILK_VIDEO_DIR") {
+            downloads.video_dir = expand_path(&dir);
+        }
+        if let Some(dir) = var("SILK_AUDIO_DIR") {
+            downloads.audio_dir = expand_path(&dir);
+        }
+        if let Some(template) = var("SILK_OUTPUT_TEMPLATE") {
+            downloads.output_template = template;
+        }
+        // `Channel=dir;Other channel=dir`
+        if let Some(channel_dirs) = var("SILK_CHANNEL_DIRS") {
+            for (channel, dir) in channel_dirs.split(';').filter_map(|e| e.split_once('=')) {
+                downloads
+                    .channel_dirs
+                    .insert(channel.trim().to_string(), dir.trim().to_string());
+            }
+        }
+        if let Some(format) = parse("SILK_AUDIO_FORMAT")? {
+            downloads.audio_format = format;
+        }
+        if let Some(quality) = var("SILK_AUDIO_QUALITY") {
+            downloads.audio_quality = quality;
+        }
+        if let Some(embed) = var("SILK_EMBED_SUBTITLES") {
+            downloads.embed_subtitles = embed != "false" && embed != "0";
+        }
+
+        let network = &mut config.network;
+        if let Some(url) = var("SILK_SPONSORBLOCK_URL") {
+            network.sponsorblock_url = url;
+        }
+        if let Some(categories) = var("SILK_SPONSORBLOCK_CATEGORIES") {
+            network.sponsorblock_categories = categories;
+        }
+
+        config
+            .validate()
+            .map_err(|(section, key, e)| format!("{}.{}: {}", section, key, e))
+    }
+}
+
+pub mod db {
+    use std::{
+        path::{Path, PathBuf},
+        str::FromStr,
+    };
+
+    use super::env;
+    use sqlx::{SqlitePool, migrate::Migrator, sqlite::SqliteConnectOptions};
+    use tokio::sync::OnceCell;
+
+    static DB: OnceCell<SqlitePool> = OnceCell::const_new();
+
+    /// The `migrations/` folder, built into the binary.
+    pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+    /// URL of the database: `path` when given, the configured database otherwise.
+    pub fn url(path: Option<&Path>) -> String {
+        match path {
+            Some(path) => format!("sqlite://{}", path.display()),
+            None => env::database_url(),
+        }
+    }
+
+    /// Opens the database at `url`, creating the file and its directory when they are missing.
+    pub async fn connect(url: &str) -> Result<SqlitePool, String> {
+        let options = SqliteConnectOptions::from_str(url)
+            .map_err(|e| format!("Invalid database URL {}: {}", url, e))?
+            .create_if_missing(true)
+            .foreign_keys(true);
+
+        if let Some(dir) = options.get_filename().parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+        }
+
+        SqlitePool::connect_with(options)
+            .await
+            .map_err(|e| format!("Cannot open the database {}: {}", url, e))
+    }
+
+    /// Opens the existing database at `url` for reading, leaving the file untouched. Returns its
+    /// path as well, and `None` when there is no database there yet.
+    pub async fn open_read_only(url: &str) -> Result<(PathBuf, Option<SqlitePool>), String> {
+        let options = SqliteConnectOptions::from_str(url)
+            .map_err(|e| format!("Invalid database URL {}: {}", url, e))?
+            .read_only(true);
+        let path = options.get_filename().to_path_buf();
+        if !path.exists() {
+            return Ok((path, None));
+        }
+
+        let pool = SqlitePool::connect_with(options)
+            .await
+            .map_err(|e| format!("Cannot open the database {}: {}", url, e))?;
+        Ok((path, Some(pool)))
+    }
+
+    /// Opens the database, `path` or the configured one, and brings its schema up to date.
+    pub async fn init(path: Option<&Path>) -> Result<(), String> {
+        let pool = connect(&url(path)).await?;
+
+        MIGRATOR
+            .run(&pool)
+            .await
+            .map_err(|e| format!("Failed to migrate the database: {}", e))?;
+
+        DB.set(pool)
+            .map_err(|_| String::from("Database has already been initialized"))
+    }
+
+    pub fn get() -> SqlitePool {
+        DB.get().expect("Database has not been initialized").clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_the_home_directory() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(expand_path("~"), home);
+        assert_eq!(expand_path("~/Videos"), home.join("Videos"));
+        assert_eq!(expand_path("~user/Videos"), PathBuf::from("~user/Videos"));
+    }
+
+    #[test]
+    fn expands_variables() {
+        let home = std::env::var("HOME").unwrap();
+        assert_eq!(expand_path("$HOME/a"), PathBuf::from(format!("{home}/a")));
+        assert_eq!(expand_path("${HOME}a"), PathBuf::from(format!("{home}a")));
+    }
+
+    #[test]
+    fn keeps_unknown_variables() {
+        assert_eq!(
+            expand_path("/a/$SILK_UNSET_VARIABLE/${SILK_UNSET_VARIABLE}/b"),
+            PathBuf::from("/a/$SILK_UNSET_VARIABLE/${SILK_UNSET_VARIABLE}/b")
+        );
+        assert_eq!(expand_path("/a/$/${HOME"), PathBuf::from("/a/$/${HOME"));
+    }
+}
@@ -0,0 +1,518 @@
+//! ## Settings
+//!
+//! Typed configuration read from `config.toml` in the config directory. Every setting has a
+//! default, so the file only needs the ones that differ, and the `SILK_*` environment variables
+//! override the file.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::OnceLock,
+};
+
+use ratatui::style::Color;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{SubtitleSource, expand_path};
+use crate::{sponsorblock, youtube::download::AudioFormat};
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub player: PlayerConfig,
+    pub downloads: DownloadsConfig,
+    pub network: NetworkConfig,
+    pub feed: FeedConfig,
+    pub ui: UiConfig,
+    pub keys: KeysConfig,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlayerConfig {
+    /// `mpv`, `vlc` or `custom`.
+    pub name: String,
+    /// Command template run by the `custom` player.
+    pub command: Option<String>,
+    /// yt-dlp format selector for video, in playback and downloads.
+    pub format: String,
+    /// yt-dlp format selector for audio-only playback and downloads.
+    pub audio_format: String,
+    /// Preferred subtitle languages, e.g. `en,de`. No subtitles when unset.
+    pub subtitle_langs: Option<String>,
+    #[serde(with = "parsed")]
+    pub subtitle_source: SubtitleSource,
+}
+
+impl Default for PlayerConfig {
+    fn default() -> Self {
+        Self {
+            name: String::from("mpv"),
+            command: None,
+            format: String::from("bestvideo*+bestaudio/best"),
+            audio_format: String::from("bestaudio/best"),
+            subtitle_langs: None,
+            subtitle_source: SubtitleSource::default(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DownloadsConfig {
+    /// Number of downloads running at the same time.
+    pub max_parallel: usize,
+    #[serde(deserialize_with = "expanded_path")]
+    pub video_dir: PathBuf,
+    #[serde(deserialize_with = "expanded_path")]
+    pub audio_dir: PathBuf,
+    /// File name template with `{field}` placeholders, e.g. `{channel}/{title}.{ext}`.
+    pub output_template: String,
+    /// Download directory per channel name.
+    pub channel_dirs: BTreeMap<String, String>,
+    #[serde(with = "parsed")]
+    pub audio_format: AudioFormat,
+    /// yt-dlp audio quality: `0` (best) to `10` for VBR, or a bitrate such as `192K`.
+    pub audio_quality: String,
+    /// Whether subtitles are embedded into video downloads rather than saved next to them.
+    pub embed_subtitles: bool,
+}
+
+impl Default for DownloadsConfig {
+    fn default() -> Self {
+        Self {
+            max_parallel: 2,
+            video_dir: dirs::video_dir().unwrap_or_else(|| expand_path("~/Videos")),
+            audio_dir: dirs::audio_dir().unwrap_or_else(|| expand_path("~/Music")),
+            output_template: String::from("{title} [{id}].{ext}"),
+            channel_dirs: BTreeMap::new(),
+            audio_format: AudioFormat::Mp3,
+            audio_quality: String::from("5"),
+            embed_subtitles: true,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// User agent of the requests that scrape YouTube pages.
+    pub user_agent: String,
+    pub sponsorblock_url: String,
+    /// Action per SponsorBlock category, e.g. `sponsor=skip,intro=mute,selfpromo=ignore`.
+    pub sponsorblock_categories: String,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            user_agent: String::from("Mozilla/5.0"),
+            sponsorblock_url: sponsorblock::DEFAULT_API_URL.to_string(),
+            sponsorblock_categories: sponsorblock::DEFAULT_CATEGORIES.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeedConfig {
+    /// How many days back new uploads of subscriptions are collected.
+    pub days: i64,
+    /// Number of videos shown in the Feed view.
+    pub limit: i64,
+    /// Age in days after which videos are removed from the feed, watched state included. `0`
+    /// keeps them forever.
+    pub retention_days: i64,
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        Self {
+            days: 7,
+            limit: 10,
+            retention_days: 90,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UiConfig {
+    /// Selected video and menu entry.
+    #[serde(with = "parsed")]
+    pub highlight: Color,
+    /// Selected channel.
+    #[serde(with = "parsed")]
+    pub channel: Color,
+    /// Selected playlist.
+    #[serde(with = "parsed")]
+    pub playlist: Color,
+    /// Tags such as the subscription mark.
+    #[serde(with = "parsed")]
+    pub tag: Color,
+    #[serde(with = "parsed")]
+    pub search: Color,
+    #[serde(with = "parsed")]
+    pub status: Color,
+    /// Matched words in offline search results.
+    #[serde(with = "parsed")]
+    pub matched: Color,
+    /// How long an unsubscribe can be undone, in seconds.
+    pub undo_seconds: u64,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            highlight: Color::Yellow,
+            channel: Color::Red,
+            playlist: Color::Green,
+            tag: Color::Blue,
+            search: Color::Green,
+            status: Color::Cyan,
+            matched: Color::Magenta,
+            undo_seconds: 10,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeysConfig {
+    pub down: char,
+    pub up: char,
+    pub play_audio: char,
+    pub pick_format: char,
+    pub subscribe: char,
+    pub unsubscribe: char,
+    pub download_video: char,
+    pub download_audio: char,
+    pub pause_download: char,
+    pub cancel_download: char,
+    pub retry_download: char,
+    pub clip: char,
+    pub audio_clip: char,
+    pub toggle_watched: char,
+    pub mark_channel_watched: char,
+    pub mark_all_watched: char,
+    pub hide_watched: char,
+    pub unwatched_first: char,
+    pub next_group: char,
+    pub edit_groups: char,
+    pub rename: char,
+    pub refresh: char,
+    pub sort: char,
+    pub delete: char,
+    pub clear_all: char,
+    pub search: char,
+    pub undo: char,
+}
+
+impl Default for KeysConfig {
+    fn default() -> Self {
+        Self {
+            down: 'j',
+            up: 'k',
+            play_audio: 'a',
+            pick_format: 'f',
+            subscribe: 's',
+            unsubscribe: 'u',
+            download_video: 'd',
+            download_audio: 'm',
+            pause_download: 'p',
+            cancel_download: 'c',
+            retry_download: 'r',
+            clip: 't',
+            audio_clip: 'T',
+            toggle_watched: 'w',
+            mark_channel_watched: 'W',
+            mark_all_watched: 'A',
+            hide_watched: 'h',
+            unwatched_first: 'o',
+            next_group: 'g',
+            edit_groups: 'G',
+            rename: 'n',
+            refresh: 'R',
+            sort: 'S',
+            delete: 'x',
+            clear_all: 'X',
+            search: '/',
+            undo: 'U',
+        }
+    }
+}
+
+impl KeysConfig {
+    fn bindings(&self) -> [(&'static str, char); 27] {
+        [
+            ("down", self.down),
+            ("up", self.up),
+            ("play_audio", self.play_audio),
+            ("pick_format", self.pick_format),
+            ("subscribe", self.subscribe),
+            ("unsubscribe", self.unsubscribe),
+            ("download_video", self.download_video),
+            ("download_audio", self.download_audio),
+            ("pause_download", self.pause_download),
+            ("cancel_download", self.cancel_download),
+            ("retry_download", self.retry_download),
+            ("clip", self.clip),
+            ("audio_clip", self.audio_clip),
+            ("toggle_watched", self.toggle_watched),
+            ("mark_channel_watched", self.mark_channel_watched),
+            ("mark_all_watched", self.mark_all_watched),
+            ("hide_watched", self.hide_watched),
+            ("unwatched_first", self.unwatched_first),
+            ("next_group", self.next_group),
+            ("edit_groups", self.edit_groups),
+            ("rename", self.rename),
+            ("refresh", self.refresh),
+            ("sort", self.sort),
+            ("delete", self.delete),
+            ("clear_all", self.clear_all),
+            ("search", self.search),
+            ("undo", self.undo),
+        ]
+    }
+}
+
+/// Settings read by `init`, or the defaults with the environment applied when it was not called.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(|| {
+        let mut config = Config::default();
+        if let Err(e) = super::env::apply(&mut config) {
+            log::warn!("{}", e);
+        }
+        config
+    })
+}
+
+/// Loads the settings for the rest of the run.
+pub fn init() -> Result<(), String> {
+    let config = load()?;
+    CONFIG
+        .set(config)
+        .map_err(|_| String::from("Settings have already been loaded"))
+}
+
+/// `config.toml` in silk's config directory.
+pub fn path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| expand_path("~/.config"))
+        .join("silk")
+        .join("config.toml")
+}
+
+/// Writes a config file with every setting commented out at its default value.
+pub fn write_template(path: &Path) -> Result<(), String> {
+    let defaults = toml::to_string(&Config::default()).map_err(|e| e.to_string())?;
+    let template = defaults
+        .lines()
+        .map(|line| match line.starts_with('[') || line.is_empty() {
+            true => line.to_string(),
+            false => format!("# {}", line),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+    }
+    fs::write(path, template + "\n").map_err(|e| format!("Cannot write {}: {}", path.display(), e))
+}
+
+/// Reads the config file, when there is one, and applies the environment on top of it.
+pub fn load() -> Result<Config, String> {
+    let path = path();
+    let source = match fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Cannot read {}: {}", path.display(), e)),
+    };
+
+    let mut config = parse(&source).map_err(|e| format!("{}: {}", path.display(), e))?;
+    super::env::apply(&mut config)?;
+    Ok(config)
+}
+
+/// Parses and validates the contents of a config file. Errors name the offending line.
+pub fn parse(source: &str) -> Result<Config, String> {
+    let config: Config = toml::from_str(source).map_err(|e| e.to_string())?;
+
+    config
+        .validate()
+        .map_err(|(section, key, e)| match line_of(source, section, key) {
+            Some(line) => format!("line {}: {}.{}: {}", line, section, key, e),
+            None => format!("{}.{}: {}", section, key, e),
+        })?;
+
+    Ok(config)
+}
+
+impl Config {
+    /// Checks what the types alone do not, returning the section and key of the first problem.
+    pub fn validate(&self) -> Result<(), (&'static str, &'static str, String)> {
+        match self.player.name.as_str() {
+            "mpv" | "vlc" => {}
+            "custom" if self.player.command.is_none() => {
+                return Err((
+                    "player",
+                    "name",
+                    String::from("custom needs player.command"),
+                ));
+            }
+            "custom" => {}
+            name => {
+                return Err((
+                    "player",
+                    "name",
+                    format!("unknown player \"{}\" (expected mpv, vlc or custom)", name),
+                ));
+            }
+        }
+
+        if self.downloads.max_parallel == 0 {
+            return Err((
+                "downloads",
+                "max_parallel",
+                String::from("must be at least 1"),
+            ));
+        }
+
+        if self.feed.days < 1 || self.feed.limit < 1 {
+            let key = if self.feed.days < 1 { "days" } else { "limit" };
+            return Err(("feed", key, String::from("must be at least 1")));
+        }
+
+        // Videos pruned while still within `days` would come back as new on every update
+        if self.feed.retention_days != 0 && self.feed.retention_days < self.feed.days {
+            return Err((
+                "feed",
+                "retention_days",
+                format!("must be 0 or at least feed.days ({})", self.feed.days),
+            ));
+        }
+
+        sponsorblock::parse_categories(&self.network.sponsorblock_categories)
+            .map_err(|e| ("network", "sponsorblock_categories", e))?;
+
+        let mut bound = HashMap::new();
+        for (action, key) in self.keys.bindings() {
+            if let Some(other) = bound.insert(key, action) {
+                return Err((
+                    "keys",
+                    action,
+                    format!("'{}' is already bound to {}", key, other),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Line number of `key` in `[section]` of a TOML document, for keys written in it.
+fn line_of(source: &str, section: &str, key: &str) -> Option<usize> {
+    let mut current = "";
+
+    for (number, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = header.trim();
+        } else if current == section
+            && let Some((name, _)) = line.split_once('=')
+            && name.trim() == key
+        {
+            return Some(number + 1);
+        }
+    }
+
+    None
+}
+
+/// Paths with `~` and `$VARS` expanded.
+fn expanded_path<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+    String::deserialize(deserializer).map(|path| expand_path(&path))
+}
+
+/// Values written as strings and parsed with `FromStr`.
+mod parsed {
+    use super::*;
+
+    pub fn serialize<T: Display, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        value
+            .parse()
+            .map_err(|e| serde::de::Error::custom(format!("\"{}\": {}", value, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> String {
+        parse(source).err().expect("config should be rejected")
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(Config::default().validate().is_ok());
+        assert!(parse("").is_ok());
+    }
+
+    #[test]
+    fn errors_name_the_line_of_the_key() {
+        let source =
+            "[player]\nname = \"mpv\"\n\n[downloads]\n# max_parallel = 2\nmax_parallel = 0\n";
+        assert_eq!(
+            error(source),
+            "line 6: downloads.max_parallel: must be at least 1"
+        );
+    }
+
+    #[test]
+    fn errors_about_unwritten_keys_have_no_line() {
+        // retention_days keeps its default of 90, which is now shorter than days
+        assert_eq!(
+            error("[feed]\ndays = 120\n"),
+            "feed.retention_days: must be 0 or at least feed.days (120)"
+        );
+    }
+
+    #[test]
+    fn rejects_keys_bound_twice() {
+        let e = error("[keys]\nup = \"j\"\n");
+        assert!(e.starts_with("line 2: keys.up:"), "{}", e);
+    }
+
+    #[test]
+    fn rejects_unknown_players() {
+        assert!(error("[player]\nname = \"nope\"\n").contains("unknown player \"nope\""));
+    }
+
+    #[test]
+    fn line_of_only_looks_in_the_section() {
+        let source = "[player]\nname = \"mpv\"\n[ keys ]\n  name = 'x'\n";
+        assert_eq!(line_of(source, "player", "name"), Some(2));
+        assert_eq!(line_of(source, "keys", "name"), Some(4));
+        assert_eq!(line_of(source, "feed", "name"), None);
+    }
+}
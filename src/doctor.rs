@@ -0,0 +1,307 @@
+//! ## Doctor
+//!
+//! `silk doctor`: checks the programs, settings, database and directories silk depends on, and
+//! says how to fix what is wrong. It runs before anything that would panic on a broken setup.
+
+use std::{collections::HashSet, path::Path};
+
+use tokio::process::Command;
+
+use crate::{
+    config::{self, data_dir, db, env, settings},
+    downloads::location,
+    types::ContentItem,
+    youtube::parse_search_results,
+};
+
+/// Search results page the parser is checked against.
+const SEARCH_FIXTURE: &str = include_str!("youtube/fixtures/search.json");
+
+#[derive(Clone, Copy, PartialEq)]
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+struct Check {
+    name: &'static str,
+    status: Status,
+    detail: String,
+    fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Warn,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Fail,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn print(&self) {
+        let label = match self.status {
+            Status::Ok => "ok",
+            Status::Warn => "warn",
+            Status::Fail => "FAIL",
+        };
+        println!("[{:<4}] {:<28} {}", label, self.name, self.detail);
+        if let Some(fix) = &self.fix {
+            println!("{:<36}-> {}", "", fix);
+        }
+    }
+}
+
+/// Runs every check, printing each result, and returns whether none of them failed. `db` is the
+/// database given with `--db`.
+pub async fn run(db: Option<&Path>) -> bool {
+    let mut checks = vec![dotenv(), settings()];
+    let config = config::get();
+
+    checks.push(tool("yt-dlp", &["--version"], yt_dlp_missing).await);
+    checks.push(tool("ffmpeg", &["-version"], ffmpeg_missing).await);
+    let missing = match config.player.name.as_str() {
+        "mpv" => player_missing,
+        _ => mpv_missing,
+    };
+    checks.push(tool("mpv", &["--version"], missing).await);
+    if config.player.name == "vlc" {
+        checks.push(tool("vlc", &["--version"], player_missing).await);
+    }
+
+    checks.push(database(&db::url(db)).await);
+    checks.push(directory("video directory", &config.downloads.video_dir));
+    checks.push(directory("audio directory", &config.downloads.audio_dir));
+    checks.push(directory("data directory", &data_dir()));
+    checks.push(parser());
+
+    for check in &checks {
+        check.print();
+    }
+
+    !checks.iter().any(|check| check.status == Status::Fail)
+}
+
+fn dotenv() -> Check {
+    match env::init() {
+        Ok(Some(path)) => Check::ok(".env", path.display().to_string()),
+        Ok(None) => Check::ok(".env", "none, settings come from the environment"),
+        Err(e) => Check::fail(
+            ".env",
+            e,
+            "fix the line above; values with spaces need quotes",
+        ),
+    }
+}
+
+/// Loads the config file and environment, falling back to the defaults when they are invalid so
+/// the other checks can still run.
+fn settings() -> Check {
+    let path = settings::path();
+
+    let check = match settings::load() {
+        Ok(_) if path.exists() => Check::ok("config", path.display().to_string()),
+        Ok(_) => Check::ok("config", "no config file, using the defaults"),
+        Err(e) => Check::fail(
+            "config",
+            e,
+            "fix the setting above, see `silk config show` for the current values",
+        ),
+    };
+
+    if check.status == Status::Ok
+        && let Err(e) = settings::init()
+    {
+        log::warn!("{}", e);
+    }
+    check
+}
+
+fn yt_dlp_missing() -> Check {
+    Check::warn(
+        "yt-dlp",
+        "not found; streams and single downloads fall back to rustube",
+        "install yt-dlp (https://github.com/yt-dlp/yt-dlp) for formats, playlists and clips",
+    )
+}
+
+fn ffmpeg_missing() -> Check {
+    Check::warn(
+        "ffmpeg",
+        "not found; yt-dlp cannot merge formats, convert audio or embed metadata",
+        "install ffmpeg from your package manager",
+    )
+}
+
+fn player_missing() -> Check {
+    Check::fail(
+        "player",
+        "the configured player is not installed",
+        "install it, or choose another one with player.name in config.toml",
+    )
+}
+
+fn mpv_missing() -> Check {
+    Check::warn(
+        "mpv",
+        "not found; only needed when player.name is mpv",
+        "install mpv (https://mpv.io/) for SponsorBlock and history tracking",
+    )
+}
+
+/// Runs `program` with `args` and reports the first line of its output as its version.
+async fn tool(program: &'static str, args: &[&str], missing: fn() -> Check) -> Check {
+    let output = match Command::new(program).args(args).output().await {
+        Ok(output) => output,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Check {
+                name: program,
+                ..missing()
+            };
+        }
+        Err(e) => {
+            return Check::fail(
+                program,
+                format!("failed to run: {}", e),
+                format!("check that {} is executable", program),
+            );
+        }
+    };
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version = stdout.lines().next().unwrap_or_default();
+    let version = version.split(" Copyright").next().unwrap_or(version).trim();
+
+    match output.status.success() {
+        true => Check::ok(program, version),
+        false => Check::fail(
+            program,
+            format!("exited with {}", output.status),
+            format!("run `{} {}` to see what is wrong", program, args.join(" ")),
+        ),
+    }
+}
+
+async fn database(url: &str) -> Check {
+    let pool = match db::open_read_only(url).await {
+        Ok((_, Some(pool))) => pool,
+        Ok((path, None)) => {
+            let dir = path.parent().unwrap_or(Path::new("."));
+            return match location::check_writable(dir) {
+                Ok(()) => Check::ok(
+                    "database",
+                    format!("{}, created the next time silk starts", path.display()),
+                ),
+                Err(e) => Check::fail(
+                    "database",
+                    e,
+                    "check DATABASE_URL or --db; the directory of the file must be writable",
+                ),
+            };
+        }
+        Err(e) => {
+            return Check::fail(
+                "database",
+                e,
+                "check DATABASE_URL or --db; the file and its directory must be writable",
+            );
+        }
+    };
+
+    let applied = sqlx::query_scalar::<_, i64>(
+        "SELECT version FROM _sqlx_migrations WHERE success ORDER BY version",
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap_or_default()
+    .into_iter()
+    .collect::<HashSet<_>>();
+
+    let pending = db::MIGRATOR
+        .iter()
+        .filter(|migration| !applied.contains(&migration.version))
+        .map(|migration| migration.description.to_string())
+        .collect::<Vec<_>>();
+
+    match pending.is_empty() {
+        true => Check::ok("database", format!("{}, up to date", url)),
+        false => Check::warn(
+            "database",
+            format!(
+                "{} pending migrations: {}",
+                pending.len(),
+                pending.join(", ")
+            ),
+            "they are applied the next time silk starts",
+        ),
+    }
+}
+
+fn directory(name: &'static str, dir: &Path) -> Check {
+    match location::check_writable(dir) {
+        Ok(()) if dir.exists() => Check::ok(name, dir.display().to_string()),
+        Ok(()) => Check::ok(
+            name,
+            format!("{}, created when first needed", dir.display()),
+        ),
+        Err(e) => Check::fail(
+            name,
+            e,
+            "fix its permissions or point the setting to another directory",
+        ),
+    }
+}
+
+/// Runs the search results parser on a bundled page, to tell a parser broken by a YouTube
+/// change from a network problem.
+fn parser() -> Check {
+    let items = serde_json::from_str(SEARCH_FIXTURE)
+        .map_err(|e| e.to_string())
+        .and_then(|json| parse_search_results(&json));
+
+    let healthy = items.as_ref().is_ok_and(|items| {
+        let video = items.iter().any(|item| match item {
+            ContentItem::Video(video) => {
+                video.id == "dQw4w9WgXcQ"
+                    && video.url == "https://www.youtube.com/watch?v=dQw4w9WgXcQ"
+                    && video.channel.username == "Rick Astley"
+            }
+            _ => false,
+        });
+        let channel = items.iter().any(|item| match item {
+            ContentItem::Channel(channel) => channel.url == "https://www.youtube.com/@RickAstleyYT",
+            _ => false,
+        });
+        video && channel
+    });
+
+    match (healthy, items) {
+        (true, _) => Check::ok("parser", "search results fixture parsed"),
+        (false, Err(e)) => Check::fail("parser", e, "this is a bug in silk, please report it"),
+        (false, Ok(_)) => Check::fail(
+            "parser",
+            "search results fixture parsed wrongly",
+            "this is a bug in silk, please report it",
+        ),
+    }
+}
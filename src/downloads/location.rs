@@ -0,0 +1,83 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::{self, expand_path},
+    youtube::download::DownloadType,
+};
+
+/// Directory a download is saved to: the channel's own directory when one is configured, the
+/// video or audio directory otherwise.
+pub fn target_dir(download_type: DownloadType, channel: Option<&str>) -> PathBuf {
+    let downloads = &config::get().downloads;
+
+    if let Some(dir) = channel.and_then(|channel| channel_dir(&downloads.channel_dirs, channel)) {
+        return dir;
+    }
+
+    match download_type {
+        DownloadType::Video => downloads.video_dir.clone(),
+        DownloadType::Audio => downloads.audio_dir.clone(),
+    }
+}
+
+/// Looks `channel` up in the per-channel directories, ignoring case.
+fn channel_dir(channel_dirs: &BTreeMap<String, String>, channel: &str) -> Option<PathBuf> {
+    channel_dirs
+        .iter()
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case(channel.trim()))
+        .map(|(_, dir)| expand_path(dir.trim()))
+}
+
+/// Checks that `dir` can be written to or, when it does not exist yet, that the nearest directory
+/// it would be created in can. Nothing is created, only permissions are looked at.
+pub fn check_writable(dir: &Path) -> Result<(), String> {
+    let existing = dir
+        .ancestors()
+        .find(|path| path.exists())
+        .ok_or(format!("No part of {} exists", dir.display()))?;
+    let metadata =
+        fs::metadata(existing).map_err(|e| format!("Cannot read {}: {}", existing.display(), e))?;
+
+    if !metadata.is_dir() {
+        return Err(format!("{} is not a directory", existing.display()));
+    }
+    if !can_write(existing) {
+        return Err(format!("{} is not writable", existing.display()));
+    }
+    Ok(())
+}
+
+/// Asks the OS whether this process may write to `path`, owner, group and ACLs included.
+#[cfg(unix)]
+fn can_write(path: &Path) -> bool {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // SAFETY: `path` is a valid NUL-terminated string that outlives the call
+    unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+}
+
+#[cfg(not(unix))]
+fn can_write(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|metadata| !metadata.permissions().readonly())
+}
+
+/// Creates `dir` if needed and makes sure files can be written to it, so that a job fails before
+/// yt-dlp starts rather than after the download.
+pub fn ensure_writable(dir: &Path) -> Result<(), String> {
+    let not_writable = |e: std::io::Error| format!("Cannot write to {}: {}", dir.display(), e);
+
+    fs::create_dir_all(dir).map_err(not_writable)?;
+
+    let probe = dir.join(".silk-write-test");
+    fs::write(&probe, b"").map_err(not_writable)?;
+    let _ = fs::remove_file(probe);
+
+    Ok(())
+}
@@ -0,0 +1,566 @@
+//! ## Downloads
+//!
+//! Download manager: owns the queue of yt-dlp jobs, runs at most the configured number at once and
+//! broadcasts every change of a job to whoever is listening. Jobs can be paused, cancelled and
+//! retried; failures are kept in SQLite until they are retried or dismissed.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    sync::{Semaphore, broadcast, oneshot},
+};
+
+use crate::{
+    config::{self, data_dir},
+    library,
+    youtube::download::{DownloadScope, DownloadType, download_command},
+};
+
+pub mod location;
+pub mod native;
+pub mod progress;
+pub mod store;
+
+pub use progress::{
+    DownloadedFile, Progress, parse_destination_line, parse_file_line, parse_item_line,
+    parse_progress_line,
+};
+
+/// Number of stderr lines kept to explain a failure.
+const STDERR_TAIL_LINES: usize = 20;
+
+pub type JobId = u64;
+
+#[derive(Clone, PartialEq)]
+pub struct DownloadRequest {
+    pub url: String,
+    pub title: String,
+    /// Uploader, used to pick the channel's download directory.
+    pub channel: Option<String>,
+    pub download_type: DownloadType,
+    pub scope: DownloadScope,
+    pub format: Option<String>,
+    /// yt-dlp `--match-filters` expression; videos it rejects are skipped.
+    pub match_filter: Option<String>,
+    /// Channel whose auto-download rule queued this, so retention knows what it may delete.
+    pub rule_channel_id: Option<String>,
+}
+
+#[derive(Clone, PartialEq)]
+pub enum JobStatus {
+    Queued,
+    Running,
+    /// Stopped by the user; resuming continues from the partial files.
+    Paused,
+    /// Stopped by the user and its partial files removed.
+    Cancelled,
+    Completed,
+    /// Finished without writing a file, e.g. because its filter rejected every video.
+    Skipped,
+    /// Failed with the tail of yt-dlp's stderr.
+    Failed(String),
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Job {
+    pub id: JobId,
+    pub request: DownloadRequest,
+    pub status: JobStatus,
+    pub progress: Progress,
+    /// Files yt-dlp announced it was writing for the video in progress.
+    pub files: Vec<PathBuf>,
+    /// How the job deviated from a regular yt-dlp download, such as a fallback to rustube.
+    pub note: Option<String>,
+}
+
+impl Job {
+    pub fn describe(&self) -> String {
+        let status = match &self.status {
+            JobStatus::Queued => String::from("Queued"),
+            JobStatus::Running => self.progress.describe(),
+            JobStatus::Paused => format!("Paused {}", self.progress.describe()),
+            JobStatus::Cancelled => String::from("Cancelled"),
+            JobStatus::Completed => String::from("Done"),
+            JobStatus::Skipped => String::from("Skipped, nothing downloaded"),
+            JobStatus::Failed(_) => String::from("Failed"),
+        };
+
+        match &self.note {
+            Some(note) => format!("{:<28} {} ({})", status, self.request.title, note),
+            None => format!("{:<28} {}", status, self.request.title),
+        }
+    }
+
+    /// Whether the job is waiting for or holding a download slot.
+    pub fn is_active(&self) -> bool {
+        matches!(self.status, JobStatus::Queued | JobStatus::Running)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Stop {
+    Pause,
+    Cancel,
+}
+
+enum Outcome {
+    Completed,
+    /// yt-dlp succeeded without announcing a single file.
+    Skipped,
+    Stopped(Stop),
+}
+
+pub struct DownloadManager {
+    jobs: Mutex<Vec<Job>>,
+    slots: Arc<Semaphore>,
+    events: broadcast::Sender<Job>,
+    next_id: AtomicU64,
+    /// Stop signal of each active job.
+    stops: Mutex<HashMap<JobId, oneshot::Sender<Stop>>>,
+}
+
+static MANAGER: OnceLock<Arc<DownloadManager>> = OnceLock::new();
+
+/// Creates the download manager and restores the failed downloads of earlier runs.
+pub async fn init() {
+    let manager = DownloadManager::new(config::get().downloads.max_parallel);
+
+    match store::load_failed().await {
+        Ok(failed) => {
+            for (request, error) in failed {
+                manager.push(request, JobStatus::Failed(error));
+            }
+        }
+        Err(e) => log::error!("{}", e),
+    }
+
+    if MANAGER.set(Arc::new(manager)).is_err() {
+        panic!("Download manager has already been initialized");
+    }
+}
+
+pub fn get() -> Arc<DownloadManager> {
+    MANAGER
+        .get()
+        .expect("Download manager has not been initialized")
+        .clone()
+}
+
+impl DownloadManager {
+    fn new(max_concurrent: usize) -> Self {
+        let (events, _) = broadcast::channel(256);
+
+        Self {
+            jobs: Mutex::new(Vec::new()),
+            slots: Arc::new(Semaphore::new(max_concurrent.max(1))),
+            events,
+            next_id: AtomicU64::new(1),
+            stops: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn push(&self, request: DownloadRequest, status: JobStatus) -> JobId {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let job = Job {
+            id,
+            request,
+            status,
+            progress: Progress::default(),
+            files: Vec::new(),
+            note: None,
+        };
+
+        self.jobs.lock().unwrap().push(job.clone());
+        let _ = self.events.send(job);
+        id
+    }
+
+    /// Adds a job to the queue. It starts as soon as a download slot is free.
+    pub fn enqueue(self: &Arc<Self>, request: DownloadRequest) -> JobId {
+        let id = self.push(request, JobStatus::Queued);
+        self.start(id);
+        id
+    }
+
+    fn start(self: &Arc<Self>, id: JobId) {
+        let (stop, stop_rx) = oneshot::channel();
+        self.stops.lock().unwrap().insert(id, stop);
+        tokio::spawn(self.clone().run(id, stop_rx));
+    }
+
+    fn job(&self, id: JobId) -> Option<Job> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|job| job.id == id)
+            .cloned()
+    }
+
+    fn stop(&self, id: JobId, stop: Stop) -> bool {
+        match self.stops.lock().unwrap().remove(&id) {
+            Some(sender) => sender.send(stop).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Pauses an active job, or resumes a paused one.
+    pub fn toggle_pause(self: &Arc<Self>, id: JobId) -> Result<(), String> {
+        let job = self.job(id).ok_or("No such download")?;
+
+        match job.status {
+            JobStatus::Queued | JobStatus::Running => {
+                self.stop(id, Stop::Pause);
+                Ok(())
+            }
+            JobStatus::Paused => {
+                self.update(id, |job| job.status = JobStatus::Queued);
+                self.start(id);
+                Ok(())
+            }
+            _ => Err(String::from("Only active downloads can be paused")),
+        }
+    }
+
+    /// Stops a job and removes its partial files.
+    pub fn cancel(&self, id: JobId) -> Result<(), String> {
+        let job = self.job(id).ok_or("No such download")?;
+
+        match job.status {
+            JobStatus::Queued | JobStatus::Running => {
+                self.stop(id, Stop::Cancel);
+                Ok(())
+            }
+            JobStatus::Paused => {
+                remove_partial_files(&job.files);
+                self.update(id, |job| job.status = JobStatus::Cancelled);
+                Ok(())
+            }
+            _ => Err(String::from("Download is not running")),
+        }
+    }
+
+    /// Queues a failed or cancelled job again.
+    pub fn retry(self: &Arc<Self>, id: JobId) -> Result<(), String> {
+        let job = self.job(id).ok_or("No such download")?;
+
+        if !matches!(job.status, JobStatus::Failed(_) | JobStatus::Cancelled) {
+            return Err(String::from(
+                "Only failed or cancelled downloads can be retried",
+            ));
+        }
+
+        self.update(id, |job| {
+            job.status = JobStatus::Queued;
+            job.progress = Progress::default();
+            job.note = None;
+        });
+        self.start(id);
+        Ok(())
+    }
+
+    /// Removes a finished job from the list, forgetting its failure.
+    pub fn dismiss(&self, id: JobId) -> Result<(), String> {
+        let job = self.job(id).ok_or("No such download")?;
+
+        if job.is_active() || job.status == JobStatus::Paused {
+            return Err(String::from("Cancel the download before dismissing it"));
+        }
+
+        self.jobs.lock().unwrap().retain(|job| job.id != id);
+        if let JobStatus::Failed(_) = job.status {
+            tokio::spawn(async move {
+                if let Err(e) = store::remove_failed(&job.request).await {
+                    log::error!("{}", e);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Snapshot of every job, oldest first.
+    pub fn jobs(&self) -> Vec<Job> {
+        self.jobs.lock().unwrap().clone()
+    }
+
+    /// Waits for a job to finish, calling `on_update` with each change.
+    pub async fn wait(&self, id: JobId, mut on_update: impl FnMut(&Job)) -> Result<(), String> {
+        let mut events = self.subscribe();
+        let mut job = self.job(id).ok_or("No such download")?;
+
+        loop {
+            on_update(&job);
+            match job.status {
+                JobStatus::Completed => return Ok(()),
+                JobStatus::Skipped => return Err(String::from("Nothing was downloaded")),
+                JobStatus::Failed(e) => return Err(e),
+                JobStatus::Cancelled => return Err(String::from("Download cancelled")),
+                _ => {}
+            }
+
+            job = loop {
+                match events.recv().await {
+                    Ok(update) if update.id == id => break update,
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => {
+                        return Err(String::from("Download manager stopped"));
+                    }
+                }
+            };
+        }
+    }
+
+    /// Receives a snapshot of a job each time it changes.
+    pub fn subscribe(&self) -> broadcast::Receiver<Job> {
+        self.events.subscribe()
+    }
+
+    fn update(&self, id: JobId, change: impl FnOnce(&mut Job)) -> Option<Job> {
+        let job = {
+            let mut jobs = self.jobs.lock().unwrap();
+            let job = jobs.iter_mut().find(|job| job.id == id)?;
+            change(job);
+            job.clone()
+        };
+
+        let _ = self.events.send(job.clone());
+        Some(job)
+    }
+
+    async fn run(self: Arc<Self>, id: JobId, mut stop: oneshot::Receiver<Stop>) {
+        let slot = tokio::select! {
+            slot = self.slots.clone().acquire_owned() => slot,
+            stop = &mut stop => {
+                self.stopped(id, stop.unwrap_or(Stop::Cancel));
+                return;
+            }
+        };
+        let Ok(_slot) = slot else {
+            return;
+        };
+
+        let Some(job) = self.update(id, |job| job.status = JobStatus::Running) else {
+            return;
+        };
+
+        let result = self.download(id, &job.request, stop).await;
+        self.stops.lock().unwrap().remove(&id);
+
+        match result {
+            Ok(outcome @ (Outcome::Completed | Outcome::Skipped)) => {
+                self.update(id, |job| {
+                    job.status = match outcome {
+                        Outcome::Skipped => JobStatus::Skipped,
+                        _ => JobStatus::Completed,
+                    }
+                });
+                if let Err(e) = store::remove_failed(&job.request).await {
+                    log::error!("{}", e);
+                }
+            }
+            Ok(Outcome::Stopped(stop)) => self.stopped(id, stop),
+            Err(e) => {
+                if let Err(e) = store::save_failed(&job.request, &e).await {
+                    log::error!("{}", e);
+                }
+                self.update(id, |job| job.status = JobStatus::Failed(e));
+            }
+        }
+    }
+
+    fn stopped(&self, id: JobId, stop: Stop) {
+        self.update(id, |job| match stop {
+            Stop::Pause => job.status = JobStatus::Paused,
+            Stop::Cancel => {
+                remove_partial_files(&job.files);
+                job.status = JobStatus::Cancelled;
+            }
+        });
+    }
+
+    async fn download(
+        &self,
+        id: JobId,
+        request: &DownloadRequest,
+        mut stop: oneshot::Receiver<Stop>,
+    ) -> Result<Outcome, String> {
+        let directory = location::target_dir(request.download_type, request.channel.as_deref());
+        location::ensure_writable(&directory)?;
+        location::ensure_writable(&data_dir())?;
+
+        // `--print` would silence the progress lines, so finished files are printed to our own
+        // stdout where there is one to name, and to a file read once yt-dlp exits elsewhere
+        #[cfg(unix)]
+        let files_output = PathBuf::from("/dev/stdout");
+        #[cfg(not(unix))]
+        let files_output =
+            std::env::temp_dir().join(format!("silk-files-{}-{}.txt", std::process::id(), id));
+        #[cfg(not(unix))]
+        let _ = fs::remove_file(&files_output);
+
+        let child = download_command(
+            &request.url,
+            request.download_type,
+            request.format.clone(),
+            &directory,
+            &request.scope,
+            request.match_filter.as_deref(),
+            &files_output,
+        )
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return self.download_natively(id, request, &directory, stop).await;
+            }
+            Err(e) => return Err(format!("Failed to run yt-dlp: {}", e)),
+        };
+
+        let stdout = child.stdout.take().ok_or("yt-dlp stdout unavailable")?;
+        let stderr = child.stderr.take().ok_or("yt-dlp stderr unavailable")?;
+        let stderr_tail = tokio::spawn(read_tail(stderr, STDERR_TAIL_LINES));
+
+        let mut lines = BufReader::new(stdout).lines();
+        let mut wrote_file = false;
+        loop {
+            let line = tokio::select! {
+                line = lines.next_line() => line,
+                stop = &mut stop => {
+                    let _ = child.kill().await;
+                    return Ok(Outcome::Stopped(stop.unwrap_or(Stop::Cancel)));
+                }
+            };
+            let Ok(Some(line)) = line else {
+                break;
+            };
+
+            if let Some(progress) = parse_progress_line(&line) {
+                self.update(id, |job| {
+                    job.progress = Progress {
+                        item: job.progress.item,
+                        ..progress
+                    }
+                });
+            } else if let Some(file) = parse_file_line(&line) {
+                wrote_file = true;
+                self.finished_file(id, &file, request).await;
+            } else if let Some(item) = parse_item_line(&line) {
+                self.update(id, |job| {
+                    job.progress.item = Some(item);
+                    job.files.clear();
+                });
+            } else if let Some(file) = parse_destination_line(&line) {
+                self.update(id, |job| job.files.push(file));
+            }
+        }
+
+        let status = child
+            .wait()
+            .await
+            .map_err(|e| format!("Lost track of yt-dlp: {}", e))?;
+        let stderr_tail = stderr_tail.await.unwrap_or_default();
+
+        #[cfg(not(unix))]
+        {
+            let printed = tokio::fs::read_to_string(&files_output)
+                .await
+                .unwrap_or_default();
+            let _ = tokio::fs::remove_file(&files_output).await;
+            for file in printed.lines().filter_map(parse_file_line) {
+                wrote_file = true;
+                self.finished_file(id, &file, request).await;
+            }
+        }
+
+        match status.success() {
+            true if !wrote_file => Ok(Outcome::Skipped),
+            true => Ok(Outcome::Completed),
+            false if stderr_tail.is_empty() => Err(format!("yt-dlp exited with {}", status)),
+            false => Err(stderr_tail),
+        }
+    }
+
+    /// Records a file yt-dlp finished in the library.
+    async fn finished_file(&self, id: JobId, file: &DownloadedFile, request: &DownloadRequest) {
+        // The item is complete, so cancelling from here on must leave its files alone
+        self.update(id, |job| job.files.clear());
+        if let Err(e) = library::add(file, request).await {
+            log::error!("{}", e);
+        }
+    }
+
+    /// Downloads `request` with rustube, for when yt-dlp is not installed.
+    async fn download_natively(
+        &self,
+        id: JobId,
+        request: &DownloadRequest,
+        directory: &Path,
+        mut stop: oneshot::Receiver<Stop>,
+    ) -> Result<Outcome, String> {
+        log::warn!("yt-dlp not found, downloading {} with rustube", request.url);
+        self.update(id, |job| {
+            job.note = Some(String::from("yt-dlp not found, downloaded with rustube"))
+        });
+
+        let download = tokio::select! {
+            download = native::NativeDownload::prepare(request, directory) => download?,
+            stop = &mut stop => return Ok(Outcome::Stopped(stop.unwrap_or(Stop::Cancel))),
+        };
+        self.update(id, |job| job.files.push(download.path().clone()));
+
+        tokio::select! {
+            result = download.run() => result?,
+            stop = &mut stop => return Ok(Outcome::Stopped(stop.unwrap_or(Stop::Cancel))),
+        }
+
+        if let Err(e) = library::add(&download.file, request).await {
+            log::error!("{}", e);
+        }
+        Ok(Outcome::Completed)
+    }
+}
+
+/// Removes the files of an unfinished download along with yt-dlp's `.part` and `.ytdl` files.
+fn remove_partial_files(files: &[PathBuf]) {
+    for file in files {
+        for suffix in ["", ".part", ".ytdl"] {
+            let mut path = file.clone().into_os_string();
+            path.push(suffix);
+            let path = Path::new(&path);
+            if path.exists()
+                && let Err(e) = fs::remove_file(path)
+            {
+                log::error!("Failed to remove {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+/// Reads `reader` to the end and returns its last `lines` lines.
+async fn read_tail(reader: impl AsyncRead + Unpin, lines: usize) -> String {
+    let mut tail = VecDeque::with_capacity(lines);
+    let mut reader = BufReader::new(reader).lines();
+
+    while let Ok(Some(line)) = reader.next_line().await {
+        if tail.len() == lines {
+            tail.pop_front();
+        }
+        tail.push_back(line);
+    }
+
+    Vec::from(tail).join("\n")
+}
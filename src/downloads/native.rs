@@ -0,0 +1,89 @@
+//! ## Native
+//!
+//! Download of a single video with rustube, used when yt-dlp is missing. It has none of yt-dlp's
+//! post-processing: audio is saved in the format YouTube serves it, without conversion or
+//! embedded metadata, and video is the best stream that carries both tracks.
+
+use std::path::{Path, PathBuf};
+
+use super::{DownloadRequest, DownloadedFile};
+use crate::youtube::{
+    download::{DownloadScope, DownloadType},
+    resolver,
+};
+
+/// Characters yt-dlp would not leave in a file name either.
+const UNSAFE_FILENAME_CHARS: [char; 9] = ['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// A video looked up by rustube and the file it is going to be saved to.
+pub struct NativeDownload {
+    video: rustube::Video,
+    download_type: DownloadType,
+    pub file: DownloadedFile,
+}
+
+impl NativeDownload {
+    /// Looks up the video of `request` and picks its file name in `directory`.
+    pub async fn prepare(request: &DownloadRequest, directory: &Path) -> Result<Self, String> {
+        if request.scope != DownloadScope::Single || request.match_filter.is_some() {
+            return Err(String::from(
+                "yt-dlp is not installed; without it only single videos can be downloaded",
+            ));
+        }
+
+        let video = resolver::fetch_video(&request.url).await?;
+        let download_type = request.download_type;
+        let stream = pick_stream(&video, download_type)?;
+        let details = video.video_details();
+
+        let extension = match (stream.mime.type_().as_str(), stream.mime.subtype().as_str()) {
+            ("audio", "mp4") => "m4a",
+            (_, subtype) => subtype,
+        };
+        let title: String = details
+            .title
+            .chars()
+            .map(|c| match UNSAFE_FILENAME_CHARS.contains(&c) {
+                true => '_',
+                false => c,
+            })
+            .collect();
+        let path = directory.join(format!("{} [{}].{}", title, video.id().as_str(), extension));
+
+        let file = DownloadedFile {
+            video_id: video.id().as_str().to_string(),
+            format: stream.itag.to_string(),
+            channel: details.author.clone(),
+            title: details.title.clone(),
+            path,
+        };
+
+        Ok(Self {
+            video,
+            download_type,
+            file,
+        })
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.file.path
+    }
+
+    pub async fn run(&self) -> Result<(), String> {
+        pick_stream(&self.video, self.download_type)?
+            .download_to(self.path())
+            .await
+            .map_err(|e| format!("rustube failed: {}", e))
+    }
+}
+
+fn pick_stream(
+    video: &rustube::Video,
+    download_type: DownloadType,
+) -> Result<&rustube::Stream, String> {
+    match download_type {
+        DownloadType::Video => video.best_quality(),
+        DownloadType::Audio => video.best_audio(),
+    }
+    .ok_or(String::from("rustube found no downloadable stream"))
+}
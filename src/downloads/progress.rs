@@ -0,0 +1,208 @@
+use std::path::PathBuf;
+
+use crate::youtube::download::{FILE_PREFIX, PROGRESS_PREFIX};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Progress {
+    pub downloaded_bytes: u64,
+    pub total_bytes: Option<u64>,
+    /// Bytes per second.
+    pub speed: Option<f64>,
+    /// Seconds left.
+    pub eta: Option<u64>,
+    /// Position in a bulk download and its number of videos.
+    pub item: Option<(usize, usize)>,
+}
+
+impl Progress {
+    pub fn percent(&self) -> Option<f64> {
+        self.total_bytes
+            .filter(|total| *total > 0)
+            .map(|total| self.downloaded_bytes as f64 * 100.0 / total as f64)
+    }
+
+    pub fn describe(&self) -> String {
+        let percent = self
+            .percent()
+            .map(|p| format!("{p:5.1}%"))
+            .unwrap_or(String::from("  ?  %"));
+        let speed = self
+            .speed
+            .map(|s| format!("{}/s", format_bytes(s)))
+            .unwrap_or_default();
+        let eta = self
+            .eta
+            .map(|eta| format!("ETA {}:{:02}", eta / 60, eta % 60))
+            .unwrap_or_default();
+
+        let item = self
+            .item
+            .map(|(index, count)| format!("{}/{} ", index, count))
+            .unwrap_or_default();
+
+        format!("{}{} {:>11} {}", item, percent, speed, eta)
+    }
+}
+
+/// Parses a progress line printed by the yt-dlp command from `download_command`.
+pub fn parse_progress_line(line: &str) -> Option<Progress> {
+    let mut fields = line
+        .trim()
+        .strip_prefix(PROGRESS_PREFIX)?
+        .split_whitespace();
+
+    let mut next = || fields.next().filter(|value| *value != "NA");
+    let downloaded_bytes = next()?.parse::<f64>().ok()? as u64;
+    let total_bytes = next().and_then(|v| v.parse::<f64>().ok());
+    let estimate = next().and_then(|v| v.parse::<f64>().ok());
+    let speed = next().and_then(|v| v.parse::<f64>().ok());
+    let eta = next().and_then(|v| v.parse::<f64>().ok());
+
+    Some(Progress {
+        downloaded_bytes,
+        total_bytes: total_bytes.or(estimate).map(|total| total as u64),
+        speed,
+        eta: eta.map(|eta| eta as u64),
+        item: None,
+    })
+}
+
+pub fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1}{}", value, UNITS[unit])
+}
+
+/// Parses the lines where yt-dlp announces a file it is about to write, so that the partial files
+/// of a cancelled download can be cleaned up.
+pub fn parse_destination_line(line: &str) -> Option<PathBuf> {
+    let line = line.trim();
+
+    if let Some(path) = line.strip_prefix("[download] Destination: ") {
+        return Some(PathBuf::from(path));
+    }
+
+    line.strip_prefix("[Merger] Merging formats into ")
+        .map(|path| PathBuf::from(path.trim_matches('"')))
+}
+
+/// Parses the line where yt-dlp starts the next video of a playlist, e.g.
+/// `[download] Downloading item 3 of 20`.
+pub fn parse_item_line(line: &str) -> Option<(usize, usize)> {
+    let rest = line.trim().strip_prefix("[download] Downloading ")?;
+    let rest = rest
+        .strip_prefix("item ")
+        .or_else(|| rest.strip_prefix("video "))?;
+    let (index, count) = rest.split_once(" of ")?;
+
+    Some((index.trim().parse().ok()?, count.trim().parse().ok()?))
+}
+
+/// File left by a finished download, as printed through `FILE_TEMPLATE`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DownloadedFile {
+    pub video_id: String,
+    pub format: String,
+    pub channel: String,
+    pub title: String,
+    pub path: PathBuf,
+}
+
+/// Parses the line yt-dlp prints once a file has been moved to its final place.
+pub fn parse_file_line(line: &str) -> Option<DownloadedFile> {
+    let mut fields = line.strip_prefix(FILE_PREFIX)?.trim_end().split('\t');
+
+    Some(DownloadedFile {
+        video_id: fields.next()?.to_string(),
+        format: fields.next()?.to_string(),
+        channel: fields.next()?.to_string(),
+        title: fields.next()?.to_string(),
+        path: PathBuf::from(fields.next()?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_progress_lines() {
+        let progress = parse_progress_line("[silk] 1048576 4194304 NA 524288.5 6").unwrap();
+        assert_eq!(
+            progress,
+            Progress {
+                downloaded_bytes: 1048576,
+                total_bytes: Some(4194304),
+                speed: Some(524288.5),
+                eta: Some(6),
+                item: None,
+            }
+        );
+        assert_eq!(progress.percent(), Some(25.0));
+    }
+
+    #[test]
+    fn falls_back_to_the_estimated_total() {
+        let progress = parse_progress_line("[silk] 100 NA 400.0 NA NA").unwrap();
+        assert_eq!(progress.total_bytes, Some(400));
+        assert_eq!(progress.speed, None);
+        assert_eq!(progress.eta, None);
+    }
+
+    #[test]
+    fn ignores_other_lines() {
+        assert_eq!(parse_progress_line("[download] Destination: a.mp4"), None);
+        assert_eq!(parse_progress_line("[silk] NA NA NA NA NA"), None);
+    }
+
+    #[test]
+    fn parses_destination_and_merger_lines() {
+        assert_eq!(
+            parse_destination_line("[download] Destination: /tmp/a b.f137.mp4"),
+            Some(PathBuf::from("/tmp/a b.f137.mp4"))
+        );
+        assert_eq!(
+            parse_destination_line("[Merger] Merging formats into \"/tmp/a b.mp4\""),
+            Some(PathBuf::from("/tmp/a b.mp4"))
+        );
+    }
+
+    #[test]
+    fn parses_item_lines() {
+        assert_eq!(
+            parse_item_line("[download] Downloading item 3 of 20"),
+            Some((3, 20))
+        );
+        assert_eq!(
+            parse_item_line("[download] Downloading video 1 of 2"),
+            Some((1, 2))
+        );
+        assert_eq!(parse_item_line("[download] Downloading playlist: x"), None);
+    }
+
+    #[test]
+    fn parses_file_lines() {
+        let line = format!(
+            "{}abc\t137+140\tSomeone\tA title\t/tmp/A title.mp4\n",
+            FILE_PREFIX
+        );
+        assert_eq!(
+            parse_file_line(&line),
+            Some(DownloadedFile {
+                video_id: String::from("abc"),
+                format: String::from("137+140"),
+                channel: String::from("Someone"),
+                title: String::from("A title"),
+                path: PathBuf::from("/tmp/A title.mp4"),
+            })
+        );
+        assert_eq!(parse_file_line("[silk-file]\tabc\t137"), None);
+    }
+}
@@ -0,0 +1,89 @@
+use chrono::Utc;
+
+use super::DownloadRequest;
+
+/// Keeps a failed download with the tail of yt-dlp's stderr, replacing any earlier failure of the
+/// same download.
+pub async fn save_failed(request: &DownloadRequest, error: &str) -> Result<(), String> {
+    let pool = crate::config::db::get();
+    let download_type = request.download_type.as_str();
+    let scope = request.scope.to_string();
+    let failed_at = Utc::now().to_rfc3339();
+
+    sqlx::query!(
+        r#"
+            INSERT INTO failed_downloads ( url, download_type, scope, title, channel, format, match_filter, rule_channel_id, error, failed_at )
+            VALUES ( ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10 )
+            ON CONFLICT(url, download_type, scope) DO UPDATE SET
+                title = excluded.title,
+                channel = excluded.channel,
+                format = excluded.format,
+                match_filter = excluded.match_filter,
+                rule_channel_id = excluded.rule_channel_id,
+                error = excluded.error,
+                failed_at = excluded.failed_at
+        "#,
+        request.url,
+        download_type,
+        scope,
+        request.title,
+        request.channel,
+        request.format,
+        request.match_filter,
+        request.rule_channel_id,
+        error,
+        failed_at
+    )
+    .execute(&pool)
+    .await
+    .map_err(|_| String::from("Failed to save failed download"))?;
+
+    Ok(())
+}
+
+pub async fn remove_failed(request: &DownloadRequest) -> Result<(), String> {
+    let pool = crate::config::db::get();
+    let download_type = request.download_type.as_str();
+    let scope = request.scope.to_string();
+
+    sqlx::query!(
+        r#"DELETE FROM failed_downloads WHERE url = ?1 AND download_type = ?2 AND scope = ?3"#,
+        request.url,
+        download_type,
+        scope
+    )
+    .execute(&pool)
+    .await
+    .map_err(|_| String::from("Failed to remove failed download"))?;
+
+    Ok(())
+}
+
+/// Failed downloads with their error, oldest first.
+pub async fn load_failed() -> Result<Vec<(DownloadRequest, String)>, String> {
+    let pool = crate::config::db::get();
+
+    let rows = sqlx::query!(
+        r#"SELECT url, download_type, scope, title, channel, format, match_filter, rule_channel_id, error FROM failed_downloads ORDER BY failed_at"#
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|_| String::from("Failed to read failed downloads"))?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            let request = DownloadRequest {
+                url: row.url,
+                title: row.title,
+                channel: row.channel,
+                download_type: row.download_type.parse().ok()?,
+                scope: row.scope.parse().ok()?,
+                format: row.format,
+                match_filter: row.match_filter,
+                rule_channel_id: row.rule_channel_id,
+            };
+            Some((request, row.error))
+        })
+        .collect())
+}
@@ -0,0 +1,181 @@
+//! ## Groups
+//!
+//! User-defined groups of subscriptions, such as "Rust" or "Music", each with a feed of its own.
+//! A subscription can be in any number of groups.
+
+use crate::types::ChannelDB;
+
+#[derive(Clone, PartialEq)]
+pub struct Group {
+    pub id: i64,
+    pub name: String,
+}
+
+pub async fn create_group(name: &str) -> Result<Group, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(String::from("A group needs a name"));
+    }
+
+    let pool = crate::config::db::get();
+    sqlx::query_as!(
+        Group,
+        r#"
+            INSERT INTO subscription_groups ( name ) VALUES ( ?1 )
+            ON CONFLICT(name) DO NOTHING
+            RETURNING id, name
+        "#,
+        name
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(|_| String::from("Failed to create group"))?
+    .ok_or(format!("Group {} already exists", name))
+}
+
+/// Deletes a group. Its subscriptions stay, only their membership goes.
+pub async fn delete_group(group_id: i64) -> Result<(), String> {
+    let pool = crate::config::db::get();
+
+    sqlx::query!(r#"DELETE FROM subscription_groups WHERE id = ?1"#, group_id)
+        .execute(&pool)
+        .await
+        .map_err(|_| String::from("Failed to delete group"))?;
+
+    Ok(())
+}
+
+/// Finds a group by name, ignoring case.
+pub async fn find_group(name: &str) -> Result<Group, String> {
+    let pool = crate::config::db::get();
+
+    sqlx::query_as!(
+        Group,
+        r#"SELECT id, name FROM subscription_groups WHERE name = ?1"#,
+        name
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(|_| String::from("Failed to read groups"))?
+    .ok_or(format!("No group named {}", name))
+}
+
+pub async fn get_groups() -> Result<Vec<Group>, String> {
+    let pool = crate::config::db::get();
+
+    sqlx::query_as!(
+        Group,
+        r#"SELECT id, name FROM subscription_groups ORDER BY name"#
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|_| String::from("Failed to read groups"))
+}
+
+pub async fn get_members(group_id: i64) -> Result<Vec<ChannelDB>, String> {
+    let pool = crate::config::db::get();
+
+    sqlx::query_as!(
+        ChannelDB,
+        r#"
+            SELECT subscriptions.channel_id, subscriptions.channel_username
+            FROM subscription_group_members AS members
+            JOIN subscriptions ON members.channel_id = subscriptions.channel_id
+            WHERE members.group_id = ?1
+            ORDER BY subscriptions.channel_username
+        "#,
+        group_id
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|_| String::from("Failed to read group members"))
+}
+
+pub async fn add_member(group_id: i64, channel_id: &str) -> Result<(), String> {
+    let pool = crate::config::db::get();
+
+    sqlx::query!(
+        r#"
+            INSERT INTO subscription_group_members ( group_id, channel_id ) VALUES ( ?1, ?2 )
+            ON CONFLICT DO NOTHING
+        "#,
+        group_id,
+        channel_id
+    )
+    .execute(&pool)
+    .await
+    .map_err(|_| String::from("Failed to add to group"))?;
+
+    Ok(())
+}
+
+pub async fn remove_member(group_id: i64, channel_id: &str) -> Result<(), String> {
+    let pool = crate::config::db::get();
+
+    sqlx::query!(
+        r#"DELETE FROM subscription_group_members WHERE group_id = ?1 AND channel_id = ?2"#,
+        group_id,
+        channel_id
+    )
+    .execute(&pool)
+    .await
+    .map_err(|_| String::from("Failed to remove from group"))?;
+
+    Ok(())
+}
+
+/// Makes `names` the groups of `channel_id`, creating those that do not exist yet.
+pub async fn set_groups(channel_id: &str, names: &[&str]) -> Result<(), String> {
+    let pool = crate::config::db::get();
+    let failed = |_| String::from("Failed to update groups");
+
+    let mut transaction = pool.begin().await.map_err(failed)?;
+
+    sqlx::query!(
+        r#"DELETE FROM subscription_group_members WHERE channel_id = ?1"#,
+        channel_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(failed)?;
+
+    for name in names
+        .iter()
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+    {
+        sqlx::query!(
+            r#"INSERT INTO subscription_groups ( name ) VALUES ( ?1 ) ON CONFLICT(name) DO NOTHING"#,
+            name
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(failed)?;
+
+        sqlx::query!(
+            r#"
+                INSERT OR IGNORE INTO subscription_group_members ( group_id, channel_id )
+                SELECT id, ?2 FROM subscription_groups WHERE name = ?1
+            "#,
+            name,
+            channel_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(failed)?;
+    }
+
+    transaction.commit().await.map_err(failed)
+}
+
+/// The group after `current` in name order, where `None` stands for the whole feed and comes
+/// before the first group and after the last.
+pub async fn next_group(current: Option<i64>) -> Result<Option<Group>, String> {
+    let groups = get_groups().await?;
+
+    let next = match current.and_then(|id| groups.iter().position(|group| group.id == id)) {
+        Some(index) => index + 1,
+        None => 0,
+    };
+    Ok(groups.into_iter().nth(next))
+}
@@ -0,0 +1,150 @@
+use chrono::Utc;
+
+use crate::types::{HistoryDB, Video};
+
+/// Share of the video that must have been played for it to count as watched.
+const COMPLETION_RATIO: f64 = 0.95;
+
+/// Records that `video` started playing and returns the position playback should resume from.
+pub async fn record_start(video: &Video) -> Result<f64, String> {
+    let pool = crate::config::db::get();
+    let started_at = Utc::now().to_rfc3339();
+
+    let last = sqlx::query!(
+        r#"SELECT last_position, completed FROM history WHERE video_id = ?1"#,
+        video.id
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(|_| String::from("Failed to read history"))?;
+
+    let resume_at = match last {
+        Some(row) if !row.completed => row.last_position,
+        _ => 0.0,
+    };
+
+    sqlx::query!(
+        r#"
+            INSERT INTO history ( video_id, title, url, channel_id, channel_username, started_at, last_position )
+            VALUES ( ?1, ?2, ?3, ?4, ?5, ?6, ?7 )
+            ON CONFLICT(video_id) DO UPDATE SET
+                title = excluded.title,
+                started_at = excluded.started_at,
+                last_position = excluded.last_position,
+                completed = FALSE
+        "#,
+        video.id,
+        video.title,
+        video.url,
+        video.channel.id,
+        video.channel.username,
+        started_at,
+        resume_at
+    )
+    .execute(&pool)
+    .await
+    .map_err(|_| String::from("Failed to record history"))?;
+
+    Ok(resume_at)
+}
+
+pub async fn update_position(
+    video_id: &str,
+    position: f64,
+    duration: Option<f64>,
+) -> Result<(), String> {
+    let pool = crate::config::db::get();
+
+    sqlx::query!(
+        r#"UPDATE history SET last_position = ?2, duration = COALESCE(?3, duration) WHERE video_id = ?1"#,
+        video_id,
+        position,
+        duration
+    )
+    .execute(&pool)
+    .await
+    .map_err(|_| String::from("Failed to update history"))?;
+
+    Ok(())
+}
+
+/// Stores the final position of a playback and returns whether the video was watched to the end.
+pub async fn finish(video_id: &str, position: f64, duration: Option<f64>) -> Result<bool, String> {
+    let completed = duration.is_some_and(|d| d > 0.0 && position >= d * COMPLETION_RATIO);
+    let position = if completed { 0.0 } else { position };
+
+    update_position(video_id, position, duration).await?;
+
+    let pool = crate::config::db::get();
+    sqlx::query!(
+        r#"UPDATE history SET completed = ?2 WHERE video_id = ?1"#,
+        video_id,
+        completed
+    )
+    .execute(&pool)
+    .await
+    .map_err(|_| String::from("Failed to update history"))?;
+
+    Ok(completed)
+}
+
+pub async fn get_history(query: &str) -> Result<Vec<HistoryDB>, String> {
+    let pool = crate::config::db::get();
+    let pattern = format!("%{}%", query);
+
+    sqlx::query_as!(
+        HistoryDB,
+        r#"
+            SELECT
+            video_id,
+            title,
+            url,
+            channel_id,
+            channel_username,
+            started_at,
+            last_position,
+            duration,
+            completed
+            FROM history
+            WHERE title LIKE ?1 OR channel_username LIKE ?1
+            ORDER BY started_at DESC;
+        "#,
+        pattern
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|_| String::from("Failed to read history"))
+}
+
+pub async fn delete(video_id: &str) -> Result<(), String> {
+    let pool = crate::config::db::get();
+
+    sqlx::query!(r#"DELETE FROM history WHERE video_id = ?1"#, video_id)
+        .execute(&pool)
+        .await
+        .map_err(|_| String::from("Failed to delete history entry"))?;
+
+    Ok(())
+}
+
+pub async fn clear() -> Result<(), String> {
+    let pool = crate::config::db::get();
+
+    sqlx::query!(r#"DELETE FROM history"#)
+        .execute(&pool)
+        .await
+        .map_err(|_| String::from("Failed to clear history"))?;
+
+    Ok(())
+}
+
+/// Formats a position in seconds as `h:mm:ss` or `m:ss`.
+pub fn format_position(seconds: f64) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    let (h, m, s) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+    match h {
+        0 => format!("{m}:{s:02}"),
+        h => format!("{h}:{m:02}:{s:02}"),
+    }
+}
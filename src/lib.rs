@@ -1,4 +1,14 @@
 pub mod config;
+pub mod doctor;
+pub mod downloads;
+pub mod groups;
+pub mod history;
+pub mod library;
+pub mod local_search;
+pub mod player;
+pub mod rules;
+pub mod sponsorblock;
+pub mod subscriptions;
 pub mod terminal;
 pub mod tui;
 pub mod types;
@@ -0,0 +1,159 @@
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+
+use crate::config::PlaybackMode;
+use crate::downloads::{DownloadRequest, DownloadedFile};
+use crate::local_search;
+use crate::types::LibraryDB;
+use crate::youtube::download::{DownloadScope, DownloadType};
+
+/// Kind of library entry for clips, which never stand in for the whole video.
+const CLIP: &str = "clip";
+
+/// Records a file written by the download manager for `request`.
+pub async fn add(file: &DownloadedFile, request: &DownloadRequest) -> Result<(), String> {
+    let pool = crate::config::db::get();
+    let path = file.path.to_string_lossy().to_string();
+    let download_type = match request.scope {
+        DownloadScope::Section(_) => CLIP,
+        _ => request.download_type.as_str(),
+    };
+    let size = std::fs::metadata(&file.path)
+        .map(|metadata| metadata.len() as i64)
+        .unwrap_or_default();
+    let downloaded_at = Utc::now().to_rfc3339();
+
+    sqlx::query!(
+        r#"
+            INSERT INTO library ( path, video_id, title, channel_username, download_type, format, size, downloaded_at, rule_channel_id )
+            VALUES ( ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9 )
+            ON CONFLICT(path) DO UPDATE SET
+                video_id = excluded.video_id,
+                title = excluded.title,
+                channel_username = excluded.channel_username,
+                download_type = excluded.download_type,
+                format = excluded.format,
+                size = excluded.size,
+                downloaded_at = excluded.downloaded_at,
+                rule_channel_id = excluded.rule_channel_id
+        "#,
+        path,
+        file.video_id,
+        file.title,
+        file.channel,
+        download_type,
+        file.format,
+        size,
+        downloaded_at,
+        request.rule_channel_id
+    )
+    .execute(&pool)
+    .await
+    .map_err(|_| String::from("Failed to add file to the library"))?;
+
+    if let Err(e) = local_search::index_transcript(&file.path).await {
+        log::warn!("{}", e);
+    }
+
+    Ok(())
+}
+
+pub async fn get_library(query: &str) -> Result<Vec<LibraryDB>, String> {
+    let pool = crate::config::db::get();
+    let pattern = format!("%{}%", query);
+
+    sqlx::query_as!(
+        LibraryDB,
+        r#"
+            SELECT
+            path,
+            video_id,
+            title,
+            channel_username,
+            download_type,
+            format,
+            size,
+            downloaded_at
+            FROM library
+            WHERE title LIKE ?1 OR channel_username LIKE ?1
+            ORDER BY downloaded_at DESC;
+        "#,
+        pattern
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|_| String::from("Failed to read library"))
+}
+
+/// Files the auto-download rule of `channel_id` downloaded, newest first.
+pub async fn get_rule_downloads(channel_id: &str) -> Result<Vec<LibraryDB>, String> {
+    let pool = crate::config::db::get();
+
+    sqlx::query_as!(
+        LibraryDB,
+        r#"
+            SELECT
+            path,
+            video_id,
+            title,
+            channel_username,
+            download_type,
+            format,
+            size,
+            downloaded_at
+            FROM library
+            WHERE rule_channel_id = ?1
+            ORDER BY downloaded_at DESC;
+        "#,
+        channel_id
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|_| String::from("Failed to read library"))
+}
+
+/// Deletes a downloaded file and its library entry.
+pub async fn delete(path: &str) -> Result<(), String> {
+    match std::fs::remove_file(path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("Failed to delete {}: {}", path, e)),
+    }
+
+    let pool = crate::config::db::get();
+    sqlx::query!(r#"DELETE FROM library WHERE path = ?1"#, path)
+        .execute(&pool)
+        .await
+        .map_err(|_| String::from("Failed to delete library entry"))?;
+
+    Ok(())
+}
+
+/// Downloaded file to play `video_id` from, if one is still on disk. Audio playback takes either
+/// kind of download, video playback only video downloads.
+pub async fn local_file(video_id: &str, mode: PlaybackMode) -> Option<PathBuf> {
+    let pool = crate::config::db::get();
+
+    let entries = sqlx::query!(
+        r#"SELECT path, download_type FROM library WHERE video_id = ?1 ORDER BY downloaded_at DESC"#,
+        video_id
+    )
+    .fetch_all(&pool)
+    .await
+    .ok()?;
+
+    let preferred: &[DownloadType] = match mode {
+        PlaybackMode::Video => &[DownloadType::Video],
+        PlaybackMode::Audio => &[DownloadType::Audio, DownloadType::Video],
+    };
+
+    preferred.iter().find_map(|download_type| {
+        entries
+            .iter()
+            .filter(|entry| entry.download_type == download_type.as_str())
+            .map(|entry| Path::new(&entry.path))
+            .find(|path| path.exists())
+            .map(Path::to_path_buf)
+    })
+}
@@ -0,0 +1,243 @@
+//! ## Local search
+//!
+//! Offline full-text search over the feed, history and library. Their titles, channel names and
+//! descriptions are indexed in the `local_search` FTS5 table, which triggers keep in step with
+//! them; downloads add the transcript from the subtitle files saved next to them.
+
+use std::path::Path;
+
+use regex::Regex;
+
+/// Marks the start of a matched term in the titles, channel names and snippets of results.
+pub const MATCH_START: char = '\u{2}';
+/// Marks the end of a matched term.
+pub const MATCH_END: char = '\u{3}';
+
+/// Highest number of rows read from the index; results merge the rows of the same video.
+const LIMIT: i64 = 50;
+
+#[derive(Clone, PartialEq)]
+pub struct LocalMatch {
+    pub video_id: String,
+    pub url: String,
+    /// Empty for videos only known from the library.
+    pub channel_id: String,
+    /// Title with the matched terms between `MATCH_START` and `MATCH_END`.
+    pub title: String,
+    /// Channel name, marked like the title.
+    pub channel: String,
+    /// Marked passage of the description or transcript, when the match is there.
+    pub snippet: Option<String>,
+    /// Where the video was found among feed, history and library, best match first.
+    pub sources: Vec<String>,
+}
+
+impl LocalMatch {
+    pub fn describe(&self) -> String {
+        let mut description = format!(
+            "{} - {} [{}]",
+            self.title,
+            self.channel,
+            self.sources.join(", ")
+        );
+        if let Some(snippet) = &self.snippet {
+            description.push_str(&format!(" · {}", snippet));
+        }
+        description
+    }
+}
+
+/// `text` without its match markers.
+pub fn unmarked(text: &str) -> String {
+    text.chars()
+        .filter(|c| *c != MATCH_START && *c != MATCH_END)
+        .collect()
+}
+
+/// FTS5 query finding the rows with every word of `input`, the last one also as a prefix since
+/// it may not be typed out yet. Words are quoted so FTS5 syntax in them is taken literally.
+fn fts_query(input: &str) -> Option<String> {
+    let words = input
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>();
+
+    let (last, rest) = words.split_last()?;
+    Some(format!("{} {}*", rest.join(" "), last).trim().to_string())
+}
+
+/// Videos matching `input`, best first. Title matches rank above channel names, then
+/// descriptions, then transcripts.
+pub async fn search(input: &str) -> Result<Vec<LocalMatch>, String> {
+    let Some(query) = fts_query(input) else {
+        return Ok(Vec::new());
+    };
+    let pool = crate::config::db::get();
+    let (start, end) = (MATCH_START.to_string(), MATCH_END.to_string());
+
+    let rows = sqlx::query!(
+        r#"
+            SELECT
+            local_search.video_id AS "video_id!: String",
+            local_search.source AS "source!: String",
+            highlight(local_search, 3, ?2, ?3) AS "title!: String",
+            highlight(local_search, 4, ?2, ?3) AS "channel!: String",
+            snippet(local_search, -1, ?2, ?3, '…', 12) AS "snippet!: String",
+            COALESCE(feed.url, history.url, 'https://www.youtube.com/watch?v=' || local_search.video_id)
+                AS "url!: String",
+            COALESCE(feed.channel, history.channel_id, '') AS "channel_id!: String"
+            FROM local_search
+            LEFT JOIN feed ON local_search.source = 'feed' AND feed.id = local_search.key
+            LEFT JOIN history ON local_search.source = 'history' AND history.video_id = local_search.key
+            WHERE local_search MATCH ?1
+            ORDER BY bm25(local_search, 0.0, 0.0, 0.0, 10.0, 4.0, 2.0, 1.0)
+            LIMIT ?4
+        "#,
+        query,
+        start,
+        end,
+        LIMIT
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|_| String::from("Failed to search offline"))?;
+
+    let mut matches: Vec<LocalMatch> = Vec::new();
+    for row in rows {
+        if let Some(existing) = matches.iter_mut().find(|m| m.video_id == row.video_id) {
+            if !existing.sources.contains(&row.source) {
+                existing.sources.push(row.source);
+            }
+            if existing.channel_id.is_empty() {
+                existing.channel_id = row.channel_id;
+            }
+            continue;
+        }
+
+        let in_heading = row.title.contains(MATCH_START) || row.channel.contains(MATCH_START);
+        matches.push(LocalMatch {
+            video_id: row.video_id,
+            url: row.url,
+            channel_id: row.channel_id,
+            title: row.title,
+            channel: row.channel,
+            snippet: (!in_heading).then_some(row.snippet),
+            sources: vec![row.source],
+        });
+    }
+
+    Ok(matches)
+}
+
+/// Indexes the transcript of the download at `path`, when subtitles were saved next to it.
+pub async fn index_transcript(path: &Path) -> Result<(), String> {
+    let Some(transcript) = read_transcript(path) else {
+        return Ok(());
+    };
+    let pool = crate::config::db::get();
+    let key = path.to_string_lossy().to_string();
+
+    sqlx::query!(
+        r#"UPDATE local_search SET transcript = ?1 WHERE source = 'library' AND key = ?2"#,
+        transcript,
+        key
+    )
+    .execute(&pool)
+    .await
+    .map_err(|_| String::from("Failed to index the transcript"))?;
+
+    Ok(())
+}
+
+/// Text of the subtitle files yt-dlp saved next to `media`, named like it with a language
+/// before the extension, e.g. `Title [id].en.vtt`.
+fn read_transcript(media: &Path) -> Option<String> {
+    let stem = media.file_stem()?.to_str()?;
+    let mut subtitles = std::fs::read_dir(media.parent()?)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().and_then(|name| name.to_str());
+            let extension = path.extension().and_then(|extension| extension.to_str());
+            name.and_then(|name| name.strip_prefix(stem))
+                .is_some_and(|rest| rest.starts_with('.'))
+                && matches!(extension, Some("vtt" | "srt"))
+        })
+        .collect::<Vec<_>>();
+    subtitles.sort();
+
+    let transcript = subtitles
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .map(|subtitles| subtitle_text(&subtitles))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    (!transcript.is_empty()).then_some(transcript)
+}
+
+/// Spoken text of a WebVTT or SubRip file, without cue numbers, timings and styling. Automatic
+/// captions repeat each line in the next cue, so repeated lines are kept once.
+fn subtitle_text(subtitles: &str) -> String {
+    let tags = Regex::new(r"<[^>]*>").expect("valid regex");
+    let mut lines: Vec<String> = Vec::new();
+
+    for line in subtitles.lines().map(str::trim) {
+        let is_header = ["WEBVTT", "Kind:", "Language:", "NOTE", "STYLE"]
+            .iter()
+            .any(|header| line.starts_with(header));
+        if line.is_empty()
+            || is_header
+            || line.contains("-->")
+            || line.chars().all(|c| c.is_ascii_digit())
+        {
+            continue;
+        }
+
+        let text = tags
+            .replace_all(line, "")
+            .replace("&amp;", "&")
+            .replace("&nbsp;", " ");
+        let text = text.trim();
+        if !text.is_empty() && lines.last().is_none_or(|last| last != text) {
+            lines.push(text.to_string());
+        }
+    }
+
+    lines.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fts_query_quotes_words_and_prefixes_the_last() {
+        assert_eq!(
+            fts_query("  rust   async tok ").as_deref(),
+            Some(r#""rust" "async" "tok"*"#)
+        );
+        assert_eq!(fts_query("tok").as_deref(), Some(r#""tok"*"#));
+    }
+
+    #[test]
+    fn fts_query_takes_fts_syntax_literally() {
+        assert_eq!(
+            fts_query(r#"a OR "b" c*"#).as_deref(),
+            Some(r#""a" "OR" """b""" "c*"*"#)
+        );
+    }
+
+    #[test]
+    fn fts_query_needs_a_word() {
+        assert_eq!(fts_query(""), None);
+        assert_eq!(fts_query(" \t "), None);
+    }
+
+    #[test]
+    fn subtitle_text_keeps_only_spoken_lines_once() {
+        let vtt = "WEBVTT\nKind: captions\nLanguage: en\n\n1\n00:00:00.000 --> 00:00:02.000\n<c>Hello</c> &amp; welcome\n\n2\n00:00:02.000 --> 00:00:04.000\nHello &amp; welcome\nto the show\n";
+        assert_eq!(subtitle_text(vtt), "Hello & welcome to the show");
+    }
+}
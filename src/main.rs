@@ -1,10 +1,16 @@
-use std::error::Error;
+use std::{error::Error, path::PathBuf};
 
 use clap::{Parser, Subcommand};
 use silk::{
-    config::{db, env},
-    terminal, tui,
-    youtube::{self, update_feed},
+    config::{PlaybackMode, db, env, settings},
+    doctor,
+    downloads::{self, DownloadRequest},
+    groups, rules, terminal, tui,
+    youtube::{
+        self,
+        download::{DownloadScope, DownloadType},
+        update_feed,
+    },
 };
 
 fn setup_logger() -> Result<(), fern::InitError> {
@@ -28,6 +34,14 @@ fn setup_logger() -> Result<(), fern::InitError> {
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+
+    /// Player to use for this run instead of the configured one (mpv, vlc or custom)
+    #[arg(long, global = true)]
+    player: Option<String>,
+
+    /// SQLite database to use instead of the configured one; created if missing
+    #[arg(long, global = true)]
+    db: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
@@ -36,31 +50,337 @@ enum Commands {
     Open {
         /// The URL of the video to play
         url: String,
+        /// Play only the audio track, without video output
+        #[arg(long)]
+        audio: bool,
+    },
+    /// Download part of a video, given as a start-end time range or a chapter title
+    Clip {
+        /// The URL of the video
+        url: String,
+        /// Time range such as 1:00-3:00, or the title of a chapter
+        section: String,
+        /// Download only the audio track
+        #[arg(long)]
+        audio: bool,
     },
+    /// Manage the rules that download new videos of subscriptions automatically
+    Rule {
+        #[command(subcommand)]
+        action: RuleAction,
+    },
+    /// Organize subscriptions into groups, each with a feed of its own
+    Group {
+        #[command(subcommand)]
+        action: GroupAction,
+    },
+    /// Check external programs, settings, database and directories, and suggest fixes
+    Doctor,
+    /// Show or edit the config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print the settings in effect, defaults and environment included
+    Show,
+    /// Open the config file in $VISUAL or $EDITOR, creating it if needed, and check it afterwards
+    Edit,
+    /// Print where the config file is read from
+    Path,
+}
+
+#[derive(Subcommand)]
+enum RuleAction {
+    /// Create or replace the rule of a subscription
+    Set {
+        /// Channel name or id of the subscription
+        channel: String,
+        /// Download only the audio track
+        #[arg(long)]
+        audio: bool,
+        /// Only download videos at least this many minutes long
+        #[arg(long)]
+        min_minutes: Option<i64>,
+        /// Only download videos whose title matches this regex
+        #[arg(long)]
+        title: Option<String>,
+        /// Keep only the latest N downloads the rule made
+        #[arg(long)]
+        keep_last: Option<i64>,
+        /// Delete downloads the rule made once older than this many days
+        #[arg(long)]
+        keep_days: Option<i64>,
+        /// Delete downloads the rule made once they have been watched
+        #[arg(long)]
+        delete_watched: bool,
+    },
+    /// List the rules
+    List,
+    /// Remove the rule of a subscription
+    Remove {
+        /// Channel name or id of the subscription
+        channel: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum GroupAction {
+    /// Create an empty group
+    Create {
+        /// Name of the group
+        name: String,
+    },
+    /// Delete a group; its subscriptions are kept
+    Delete {
+        /// Name of the group
+        name: String,
+    },
+    /// Add subscriptions to a group
+    Add {
+        /// Name of the group
+        group: String,
+        /// Channel names or ids of the subscriptions
+        #[arg(required = true)]
+        channels: Vec<String>,
+    },
+    /// Remove subscriptions from a group
+    Remove {
+        /// Name of the group
+        group: String,
+        /// Channel names or ids of the subscriptions
+        #[arg(required = true)]
+        channels: Vec<String>,
+    },
+    /// List the groups, or the subscriptions in one
+    List {
+        /// Name of the group
+        group: Option<String>,
+    },
+}
+
+/// Prints `error` and exits, for errors with several lines that returning them from `main` would
+/// print on one.
+fn exit_with(error: String) -> ! {
+    eprintln!("Error: {}", error);
+    std::process::exit(1);
+}
+
+async fn download_clip(url: String, section: String, audio: bool) -> Result<(), String> {
+    let scope = DownloadScope::parse_section_input(&section)?;
+    let manager = downloads::get();
+
+    let id = manager.enqueue(DownloadRequest {
+        title: format!("{} ({})", url, scope.section_label().unwrap_or_default()),
+        url,
+        channel: None,
+        download_type: match audio {
+            true => DownloadType::Audio,
+            false => DownloadType::Video,
+        },
+        scope,
+        format: None,
+        match_filter: None,
+        rule_channel_id: None,
+    });
+
+    let result = manager
+        .wait(id, |job| eprint!("\r\x1b[2K{}", job.describe()))
+        .await;
+    eprintln!();
+    result
+}
+
+async fn run_config_action(action: ConfigAction) -> Result<(), String> {
+    let path = settings::path();
+
+    match action {
+        ConfigAction::Show => {
+            let config = settings::load()?;
+            let shown = toml::to_string(&config).map_err(|e| e.to_string())?;
+            print!("{}", shown);
+        }
+        ConfigAction::Edit => {
+            if !path.exists() {
+                settings::write_template(&path)?;
+            }
+
+            let editor = std::env::var("VISUAL")
+                .or_else(|_| std::env::var("EDITOR"))
+                .unwrap_or(String::from("vi"));
+            let mut editor = editor.split_whitespace();
+            let program = editor.next().ok_or("The editor command is empty")?;
+
+            let status = tokio::process::Command::new(program)
+                .args(editor)
+                .arg(&path)
+                .status()
+                .await
+                .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+            if !status.success() {
+                return Err(format!("{} exited with {}", program, status));
+            }
+
+            settings::load()?;
+            println!("{} is valid", path.display());
+        }
+        ConfigAction::Path => println!("{}", path.display()),
+    }
+
+    Ok(())
+}
+
+async fn run_rule_action(action: RuleAction) -> Result<(), String> {
+    match action {
+        RuleAction::Set {
+            channel,
+            audio,
+            min_minutes,
+            title,
+            keep_last,
+            keep_days,
+            delete_watched,
+        } => {
+            let subscription = rules::find_subscription(&channel).await?;
+            let rule = rules::Rule {
+                channel_id: subscription.channel_id,
+                channel_username: subscription.channel_username,
+                audio_only: audio,
+                min_duration: min_minutes.map(|minutes| minutes * 60),
+                title_pattern: title,
+                keep_last,
+                keep_days,
+                delete_watched,
+            };
+            rules::set_rule(&rule).await?;
+            println!("{}", rule.describe());
+        }
+        RuleAction::List => {
+            for rule in rules::get_rules().await? {
+                println!("{}", rule.describe());
+            }
+        }
+        RuleAction::Remove { channel } => {
+            let subscription = rules::find_subscription(&channel).await?;
+            rules::remove_rule(&subscription.channel_id).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_group_action(action: GroupAction) -> Result<(), String> {
+    match action {
+        GroupAction::Create { name } => {
+            groups::create_group(&name).await?;
+        }
+        GroupAction::Delete { name } => {
+            let group = groups::find_group(&name).await?;
+            groups::delete_group(group.id).await?;
+        }
+        GroupAction::Add { group, channels } => {
+            let group = groups::find_group(&group).await?;
+            for channel in channels {
+                let subscription = rules::find_subscription(&channel).await?;
+                groups::add_member(group.id, &subscription.channel_id).await?;
+            }
+        }
+        GroupAction::Remove { group, channels } => {
+            let group = groups::find_group(&group).await?;
+            for channel in channels {
+                let subscription = rules::find_subscription(&channel).await?;
+                groups::remove_member(group.id, &subscription.channel_id).await?;
+            }
+        }
+        GroupAction::List { group: Some(name) } => {
+            let group = groups::find_group(&name).await?;
+            for member in groups::get_members(group.id).await? {
+                println!("{}", member.channel_username);
+            }
+        }
+        GroupAction::List { group: None } => {
+            for group in groups::get_groups().await? {
+                let members = groups::get_members(group.id).await?;
+                println!("{} ({} channels)", group.name, members.len());
+            }
+        }
+    }
+
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     setup_logger()?;
 
-    env::Env::init();
-    db::init().await;
+    let cli = Cli::parse();
 
-    update_feed().await;
+    // Before anything that panics on a broken setup
+    if let Some(Commands::Doctor) = cli.command {
+        let healthy = doctor::run(cli.db.as_deref()).await;
+        std::process::exit(if healthy { 0 } else { 1 });
+    }
 
-    let cli = Cli::parse();
+    env::init().unwrap_or_else(|e| exit_with(e));
+    if let Some(Commands::Config { action }) = cli.command {
+        run_config_action(action)
+            .await
+            .unwrap_or_else(|e| exit_with(e));
+        return Ok(());
+    }
+    settings::init().unwrap_or_else(|e| exit_with(e));
+    db::init(cli.db.as_deref())
+        .await
+        .unwrap_or_else(|e| exit_with(e));
+    downloads::init().await;
+
+    match cli.command {
+        Some(Commands::Rule { action }) => return Ok(run_rule_action(action).await?),
+        Some(Commands::Group { action }) => return Ok(run_group_action(action).await?),
+        Some(Commands::Clip {
+            url,
+            section,
+            audio,
+        }) => return Ok(download_clip(url, section, audio).await?),
+        _ => {}
+    }
+
+    if let Err(e) = update_feed().await {
+        log::error!("{}", e);
+    }
+
+    if let Some(name) = &cli.player {
+        silk::player::set_override(name)?;
+    }
 
     let mut terminal = terminal::init()?;
 
-    match cli.command {
-        Some(Commands::Open { url }) => {
-            youtube::play_video(&mut terminal, &url).await?;
+    let result = match cli.command {
+        Some(Commands::Open { url, audio }) => {
+            let mode = match audio {
+                true => PlaybackMode::Audio,
+                false => PlaybackMode::Video,
+            };
+            youtube::play_video(&mut terminal, &url, mode).await
+        }
+        Some(
+            Commands::Rule { .. }
+            | Commands::Group { .. }
+            | Commands::Clip { .. }
+            | Commands::Doctor
+            | Commands::Config { .. },
+        ) => {
+            unreachable!("handled before the terminal is set up")
         }
         None => {
             tui::tui::main();
+            Ok(())
         }
-    }
+    };
 
-    terminal::exit(&mut terminal)?;
+    terminal::exit(&mut terminal, result)?;
     Ok(())
 }
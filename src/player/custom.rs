@@ -0,0 +1,197 @@
+use super::{PlayRequest, Player};
+
+/// Player started from a user supplied command template such as
+/// `celluloid --mpv-start={start} {url}`.
+///
+/// The template is split into words once, before any substitution, and every placeholder is
+/// replaced inside its own argument, so values containing spaces or quotes stay a single argument.
+/// Supported placeholders: `{url}`, `{audio_url}`, `{title}`, `{start}` and `{sub_langs}`. A word
+/// holding `{audio_url}` or `{sub_langs}` is dropped when there is no such value.
+pub struct Custom {
+    words: Vec<String>,
+}
+
+impl Custom {
+    pub fn new(template: &str) -> Result<Self, String> {
+        let words = split_words(template)?;
+
+        if words.is_empty() {
+            return Err(String::from("The custom player command is empty"));
+        }
+        if !words.iter().any(|word| word.contains("{url}")) {
+            return Err(String::from(
+                "The custom player command must contain a {url} placeholder",
+            ));
+        }
+
+        Ok(Self { words })
+    }
+}
+
+impl Player for Custom {
+    fn name(&self) -> &str {
+        &self.words[0]
+    }
+
+    fn args(&self, request: &PlayRequest) -> Vec<String> {
+        self.words
+            .iter()
+            .filter(|word| request.audio_url.is_some() || !word.contains("{audio_url}"))
+            .filter(|word| request.subtitle_langs.is_some() || !word.contains("{sub_langs}"))
+            .map(|word| substitute(word, request))
+            .collect()
+    }
+}
+
+/// Replaces the placeholders of `word` in a single pass, so text a value brings in, such as a
+/// title containing `{url}`, is never substituted again.
+fn substitute(word: &str, request: &PlayRequest) -> String {
+    let mut result = String::with_capacity(word.len());
+    let mut rest = word;
+
+    while let Some(open) = rest.find('{') {
+        result.push_str(&rest[..open]);
+        rest = &rest[open..];
+
+        let value = rest.find('}').and_then(|close| {
+            let value = match &rest[..=close] {
+                "{url}" => request.url.to_string(),
+                "{audio_url}" => request.audio_url.unwrap_or_default().to_string(),
+                "{title}" => request.title.to_string(),
+                "{start}" => request.start.to_string(),
+                "{sub_langs}" => request.subtitle_langs.unwrap_or_default().to_string(),
+                _ => return None,
+            };
+            Some((value, close + 1))
+        });
+
+        match value {
+            Some((value, len)) => {
+                result.push_str(&value);
+                rest = &rest[len..];
+            }
+            None => {
+                result.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// Splits a command template on whitespace, keeping single or double quoted sections together.
+fn split_words(template: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+
+    for ch in template.chars() {
+        match (quote, ch) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(ch);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err(String::from(
+            "Unterminated quote in the custom player command",
+        ));
+    }
+    if in_word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{PlaybackMode, SubtitleSource};
+
+    fn request<'a>(title: &'a str, audio_url: Option<&'a str>) -> PlayRequest<'a> {
+        PlayRequest {
+            url: "https://example.com/video",
+            audio_url,
+            title,
+            mode: PlaybackMode::Video,
+            format: None,
+            start: 12.5,
+            ipc_socket: None,
+            subtitle_langs: None,
+            subtitle_source: SubtitleSource::Manual,
+            interactive: false,
+        }
+    }
+
+    #[test]
+    fn split_words_keeps_quoted_sections_together() {
+        let words = split_words(r#"player --title="{title}" 'two words'  {url}"#).unwrap();
+        assert_eq!(words, ["player", "--title={title}", "two words", "{url}"]);
+    }
+
+    #[test]
+    fn split_words_rejects_unterminated_quotes() {
+        assert!(split_words("player '{url}").is_err());
+    }
+
+    #[test]
+    fn values_are_not_substituted_again() {
+        let player = Custom::new("player --start={start} --title={title} {url}").unwrap();
+        let args = player.args(&request("{url} and {start}", None));
+        assert_eq!(
+            args,
+            [
+                "player",
+                "--start=12.5",
+                "--title={url} and {start}",
+                "https://example.com/video",
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_braces_are_kept() {
+        assert!(Custom::new("player {nope} {url").is_err());
+
+        let player = Custom::new("player {nope} {{url}}").unwrap();
+        assert_eq!(
+            player.args(&request("", None)),
+            ["player", "{nope}", "{https://example.com/video}"]
+        );
+    }
+
+    #[test]
+    fn words_without_a_value_are_dropped() {
+        let player = Custom::new("player {url} --audio-file={audio_url}").unwrap();
+        assert_eq!(
+            player.args(&request("", None)),
+            ["player", "https://example.com/video"]
+        );
+        assert_eq!(
+            player.args(&request("", Some("https://example.com/audio"))),
+            [
+                "player",
+                "https://example.com/video",
+                "--audio-file=https://example.com/audio",
+            ]
+        );
+    }
+}
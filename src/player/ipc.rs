@@ -0,0 +1,95 @@
+//! ## Ipc
+//!
+//! Minimal client for mpv's JSON IPC protocol (`--input-ipc-server`).
+
+use std::{io, path::Path, time::Duration};
+
+use serde_json::{Value, json};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{
+        UnixStream,
+        unix::{OwnedReadHalf, OwnedWriteHalf},
+    },
+    time::sleep,
+};
+
+pub struct MpvIpc {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+    request_id: u64,
+}
+
+impl MpvIpc {
+    /// Connects to the socket created by mpv, waiting up to `timeout` for it to show up since mpv
+    /// only creates it once it has started.
+    pub async fn connect(path: &Path, timeout: Duration) -> io::Result<Self> {
+        let step = Duration::from_millis(100);
+        let mut waited = Duration::ZERO;
+
+        loop {
+            match UnixStream::connect(path).await {
+                Ok(stream) => {
+                    let (reader, writer) = stream.into_split();
+                    return Ok(Self {
+                        reader: BufReader::new(reader),
+                        writer,
+                        request_id: 0,
+                    });
+                }
+                Err(e) if waited >= timeout => return Err(e),
+                Err(_) => {
+                    sleep(step).await;
+                    waited += step;
+                }
+            }
+        }
+    }
+
+    pub async fn get_property(&mut self, name: &str) -> io::Result<Value> {
+        self.command(json!(["get_property", name])).await
+    }
+
+    pub async fn set_property(&mut self, name: &str, value: Value) -> io::Result<()> {
+        self.command(json!(["set_property", name, value]))
+            .await
+            .map(|_| ())
+    }
+
+    /// Sends a command and waits for its reply, skipping the event lines mpv interleaves with
+    /// replies.
+    async fn command(&mut self, command: Value) -> io::Result<Value> {
+        self.request_id += 1;
+
+        let mut request = json!({ "command": command, "request_id": self.request_id }).to_string();
+        request.push('\n');
+        self.writer.write_all(request.as_bytes()).await?;
+
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.reader.read_line(&mut line).await? == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "mpv closed the IPC socket",
+                ));
+            }
+
+            let Ok(reply) = serde_json::from_str::<Value>(&line) else {
+                continue;
+            };
+
+            if reply["request_id"].as_u64() != Some(self.request_id) {
+                continue;
+            }
+
+            return match reply["error"].as_str() {
+                Some("success") => Ok(reply["data"].clone()),
+                error => Err(io::Error::other(format!(
+                    "mpv: {}",
+                    error.unwrap_or("unknown error")
+                ))),
+            };
+        }
+    }
+}
@@ -0,0 +1,360 @@
+//! ## Player
+//!
+//! Media players silk can hand videos to. Players are always started from an argument vector,
+//! never through a shell. mpv is additionally driven over IPC so the watch history can follow
+//! the position.
+
+use std::{
+    io,
+    path::Path,
+    process::{ExitStatus, Stdio},
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use tokio::{
+    process::{Child, Command},
+    time::sleep,
+};
+
+use crate::{
+    config::{self, PlaybackMode, SubtitleSource},
+    history, library,
+    sponsorblock::{self, SegmentAction},
+    types::Video,
+    youtube::{self, resolver},
+};
+
+pub mod custom;
+#[cfg(unix)]
+pub mod ipc;
+pub mod mpv;
+pub mod vlc;
+
+pub use custom::Custom;
+pub use mpv::Mpv;
+pub use vlc::Vlc;
+
+/// How often the position is checked against SponsorBlock segments.
+const TICK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often the position is written to the watch history.
+const HISTORY_INTERVAL: Duration = Duration::from_secs(2);
+
+static PLAYER_OVERRIDE: OnceLock<String> = OnceLock::new();
+
+/// Everything a player needs to start playback.
+pub struct PlayRequest<'a> {
+    /// Page URL when the player resolves it itself, stream URL otherwise.
+    pub url: &'a str,
+    /// Separate audio stream to play along with `url`, for merged formats.
+    pub audio_url: Option<&'a str>,
+    pub title: &'a str,
+    pub mode: PlaybackMode,
+    /// yt-dlp format selector, for players that resolve page URLs.
+    pub format: Option<&'a str>,
+    /// Position to start from, in seconds.
+    pub start: f64,
+    pub ipc_socket: Option<&'a Path>,
+    /// Preferred subtitle languages, e.g. `en,de`.
+    pub subtitle_langs: Option<&'a str>,
+    pub subtitle_source: SubtitleSource,
+    /// Whether the player may read keys from the terminal, as with `silk open`. Inside the TUI it
+    /// must stay detached from it.
+    pub interactive: bool,
+}
+
+pub trait Player: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Program followed by its arguments.
+    fn args(&self, request: &PlayRequest) -> Vec<String>;
+
+    /// Whether the player can open YouTube page URLs itself; otherwise it is handed stream URLs.
+    fn resolves_page_urls(&self) -> bool {
+        false
+    }
+
+    /// Whether the player accepts `PlayRequest::ipc_socket` and speaks mpv's IPC protocol on it.
+    fn supports_ipc(&self) -> bool {
+        false
+    }
+
+    fn spawn(&self, request: &PlayRequest) -> io::Result<Child> {
+        let args = self.args(request);
+        let (program, args) = args
+            .split_first()
+            .ok_or(io::Error::other("Empty player command"))?;
+
+        let stdin = match request.interactive {
+            true => Stdio::inherit(),
+            false => Stdio::null(),
+        };
+
+        Command::new(program)
+            .args(args)
+            .stdin(stdin)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+    }
+}
+
+/// Builds the player called `name`: `mpv`, `vlc` or `custom`, which runs `SILK_PLAYER_COMMAND`.
+pub fn from_name(name: &str) -> Result<Box<dyn Player>, String> {
+    match name {
+        "mpv" => Ok(Box::new(Mpv)),
+        "vlc" => Ok(Box::new(Vlc)),
+        "custom" => {
+            let template = config::get().player.command.clone().ok_or(String::from(
+                "player.command (SILK_PLAYER_COMMAND) is not set",
+            ))?;
+            Ok(Box::new(Custom::new(&template)?))
+        }
+        name => Err(format!("Unknown player \"{}\"", name)),
+    }
+}
+
+/// Uses `name` instead of the configured player for the rest of this run.
+pub fn set_override(name: &str) -> Result<(), String> {
+    from_name(name)?;
+    PLAYER_OVERRIDE
+        .set(name.to_string())
+        .map_err(|_| String::from("The player has already been chosen"))
+}
+
+/// The player chosen for this run, falling back to mpv when the configured one is invalid.
+pub fn current() -> Box<dyn Player> {
+    let name = PLAYER_OVERRIDE
+        .get()
+        .cloned()
+        .unwrap_or_else(|| config::get().player.name.clone());
+
+    from_name(&name).unwrap_or_else(|e| {
+        log::warn!("{e}, falling back to mpv");
+        Box::new(Mpv)
+    })
+}
+
+fn exit_result(player: &dyn Player, status: ExitStatus) -> Result<(), String> {
+    match status.success() {
+        true => Ok(()),
+        false => Err(format!("{} exited with {}", player.name(), status)),
+    }
+}
+
+/// Plays `video`, resuming from the last position in history, and returns once the player exits.
+/// With players that support IPC the progress is recorded while it plays, and SponsorBlock
+/// segments are skipped or muted. `notify` is told when streaming falls back from yt-dlp.
+pub async fn play(
+    video: &Video,
+    mode: PlaybackMode,
+    format: Option<String>,
+    notify: impl Fn(String),
+) -> Result<(), String> {
+    // A picked format asks for that stream, otherwise a downloaded copy wins over streaming
+    let local_file = match format {
+        Some(_) => None,
+        None => library::local_file(&video.id, mode).await,
+    };
+    play_from(video, mode, format, local_file.as_deref(), false, notify).await
+}
+
+/// Plays `video` like `play`, with the player reading keys from the terminal, for `silk open`.
+pub async fn play_interactive(
+    video: &Video,
+    mode: PlaybackMode,
+    notify: impl Fn(String),
+) -> Result<(), String> {
+    let local_file = library::local_file(&video.id, mode).await;
+    play_from(video, mode, None, local_file.as_deref(), true, notify).await
+}
+
+/// Plays the downloaded `file` of `video`, like `play`.
+pub async fn play_file(
+    video: &Video,
+    file: &Path,
+    mode: PlaybackMode,
+    notify: impl Fn(String),
+) -> Result<(), String> {
+    if !file.exists() {
+        return Err(format!("{} no longer exists", file.display()));
+    }
+    play_from(video, mode, None, Some(file), false, notify).await
+}
+
+async fn play_from(
+    video: &Video,
+    mode: PlaybackMode,
+    format: Option<String>,
+    local_file: Option<&Path>,
+    interactive: bool,
+    notify: impl Fn(String),
+) -> Result<(), String> {
+    let player = current();
+    let resume_at = history::record_start(video).await.unwrap_or(0.0);
+    let format = format.unwrap_or_else(|| mode.format());
+
+    // Players that resolve page URLs do so through yt-dlp, so without it they get streams too
+    let resolves_page_urls = player.resolves_page_urls() && resolver::yt_dlp_available().await;
+    let page_url = local_file.is_none() && resolves_page_urls;
+    let (url, audio_url) = match (local_file, resolves_page_urls) {
+        (Some(path), _) => (path.to_string_lossy().to_string(), None),
+        (None, true) => (video.url.clone(), None),
+        (None, false) => {
+            let stream = resolver::resolve(&video.url, &format, mode).await?;
+            if let Some(notice) = stream.notice() {
+                notify(notice);
+            }
+            (stream.url, stream.audio_url)
+        }
+    };
+
+    let source = Source {
+        url: &url,
+        audio_url: audio_url.as_deref(),
+        format: &format,
+        start: resume_at,
+        interactive,
+    };
+    let mut watched = watch(player.as_ref(), video, mode, &source).await?;
+
+    // yt-dlp inside the player can fail on a video the resolvers still find streams for
+    if page_url && !watched.started && !watched.status.success() {
+        let exit_error = exit_result(player.as_ref(), watched.status).unwrap_err();
+        let stream = resolver::resolve(&video.url, &format, mode)
+            .await
+            .map_err(|e| format!("{}; {}", exit_error, e))?;
+        notify(stream.notice().unwrap_or_else(|| {
+            format!(
+                "{} could not open the page; streaming with {}",
+                player.name(),
+                stream.resolver
+            )
+        }));
+
+        let source = Source {
+            url: &stream.url,
+            audio_url: stream.audio_url.as_deref(),
+            ..source
+        };
+        watched = watch(player.as_ref(), video, mode, &source).await?;
+    }
+
+    if let Ok(true) = history::finish(&video.id, watched.position, watched.duration).await {
+        let _ = youtube::set_watched(&video.id, true).await;
+    }
+
+    exit_result(player.as_ref(), watched.status)
+}
+
+/// What a player is started on.
+struct Source<'a> {
+    url: &'a str,
+    audio_url: Option<&'a str>,
+    format: &'a str,
+    start: f64,
+    /// Whether the player reads keys from the terminal.
+    interactive: bool,
+}
+
+/// How a run of the player went.
+struct Watched {
+    status: ExitStatus,
+    /// Whether the player reported a position, i.e. the video actually opened.
+    started: bool,
+    position: f64,
+    duration: Option<f64>,
+}
+
+/// Runs `player` on `source` until it exits, following it over IPC when it supports that.
+async fn watch(
+    player: &dyn Player,
+    video: &Video,
+    mode: PlaybackMode,
+    source: &Source<'_>,
+) -> Result<Watched, String> {
+    let socket = std::env::temp_dir().join(format!("silk-mpv-{}.sock", video.id));
+    let ipc_socket = (cfg!(unix) && player.supports_ipc()).then_some(socket.as_path());
+
+    let config = config::get();
+    let mut child = player
+        .spawn(&PlayRequest {
+            url: source.url,
+            audio_url: source.audio_url,
+            title: &video.title,
+            mode,
+            format: Some(source.format),
+            start: source.start,
+            ipc_socket,
+            subtitle_langs: config.player.subtitle_langs.as_deref(),
+            subtitle_source: config.player.subtitle_source,
+            interactive: source.interactive,
+        })
+        .map_err(|e| format!("Failed to start {}: {}", player.name(), e))?;
+
+    let mut position = source.start;
+    let mut duration = None;
+    let mut started = false;
+
+    #[cfg(unix)]
+    if let Some(socket) = ipc_socket {
+        let segments = sponsorblock::segments(&video.id).await.unwrap_or_else(|e| {
+            log::warn!("SponsorBlock unavailable for {}: {e}", video.id);
+            Vec::new()
+        });
+
+        if let Ok(mut ipc) = ipc::MpvIpc::connect(socket, Duration::from_secs(10)).await {
+            let mut muted_by_us = false;
+            let mut last_saved = Instant::now();
+
+            loop {
+                tokio::select! {
+                    _ = child.wait() => break,
+                    _ = sleep(TICK_INTERVAL) => {
+                        if let Some(pos) = ipc.get_property("time-pos").await.ok().and_then(|pos| pos.as_f64()) {
+                            position = pos;
+                            started = true;
+                        }
+
+                        match segments.iter().find(|s| s.contains(position)) {
+                            Some(segment) if segment.action == SegmentAction::Skip => {
+                                let _ = ipc.set_property("time-pos", segment.end.into()).await;
+                            }
+                            Some(segment) if segment.action == SegmentAction::Mute && !muted_by_us => {
+                                muted_by_us = ipc.set_property("mute", true.into()).await.is_ok();
+                            }
+                            None if muted_by_us => {
+                                let _ = ipc.set_property("mute", false.into()).await;
+                                muted_by_us = false;
+                            }
+                            _ => {}
+                        }
+
+                        if last_saved.elapsed() >= HISTORY_INTERVAL {
+                            if let Ok(total) = ipc.get_property("duration").await {
+                                duration = total.as_f64().or(duration);
+                            }
+                            let _ = history::update_position(&video.id, position, duration).await;
+                            last_saved = Instant::now();
+                        }
+                    }
+                }
+            }
+        }
+        let _ = std::fs::remove_file(socket);
+    }
+
+    let status = child
+        .wait()
+        .await
+        .map_err(|e| format!("Lost track of {}: {}", player.name(), e))?;
+
+    Ok(Watched {
+        status,
+        started,
+        position,
+        duration,
+    })
+}
@@ -0,0 +1,58 @@
+use super::{PlayRequest, Player};
+use crate::config::PlaybackMode;
+
+pub struct Mpv;
+
+impl Player for Mpv {
+    fn name(&self) -> &str {
+        "mpv"
+    }
+
+    fn args(&self, request: &PlayRequest) -> Vec<String> {
+        let mut args = vec![
+            String::from("mpv"),
+            format!("--start={}", request.start),
+            format!("--force-media-title={}", request.title),
+        ];
+
+        if !request.interactive {
+            args.push(String::from("--no-terminal"));
+        }
+        if let PlaybackMode::Audio = request.mode {
+            args.push(String::from("--no-video"));
+        }
+        if let Some(format) = request.format {
+            args.push(format!("--ytdl-format={}", format));
+        }
+        if let Some(audio_url) = request.audio_url {
+            args.push(format!("--audio-file={}", audio_url));
+        }
+        if let Some(langs) = request.subtitle_langs {
+            // mpv picks the track, yt-dlp has to be asked to list the tracks at all
+            args.push(format!("--slang={}", langs));
+            args.push(format!("--ytdl-raw-options-append=sub-langs={}", langs));
+            if request.subtitle_source.manual() {
+                args.push(String::from("--ytdl-raw-options-append=write-subs="));
+            }
+            if request.subtitle_source.auto() {
+                args.push(String::from("--ytdl-raw-options-append=write-auto-subs="));
+            }
+        }
+        if let Some(socket) = request.ipc_socket {
+            args.push(format!("--input-ipc-server={}", socket.display()));
+        }
+
+        // Everything after `--` is a file, so a URL can never be read as an option.
+        args.push(String::from("--"));
+        args.push(request.url.to_string());
+        args
+    }
+
+    fn resolves_page_urls(&self) -> bool {
+        true
+    }
+
+    fn supports_ipc(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,34 @@
+use super::{PlayRequest, Player};
+use crate::config::PlaybackMode;
+
+pub struct Vlc;
+
+impl Player for Vlc {
+    fn name(&self) -> &str {
+        "vlc"
+    }
+
+    fn args(&self, request: &PlayRequest) -> Vec<String> {
+        let mut args = vec![
+            String::from("vlc"),
+            String::from("--play-and-exit"),
+            format!("--start-time={}", request.start),
+            format!("--meta-title={}", request.title),
+        ];
+
+        if let PlaybackMode::Audio = request.mode {
+            args.push(String::from("--no-video"));
+            args.push(String::from("--intf=dummy"));
+        }
+        if let Some(langs) = request.subtitle_langs {
+            args.push(format!("--sub-language={}", langs));
+        }
+        if let Some(audio_url) = request.audio_url {
+            args.push(format!("--input-slave={}", audio_url));
+        }
+
+        args.push(String::from("--"));
+        args.push(request.url.to_string());
+        args
+    }
+}
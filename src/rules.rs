@@ -0,0 +1,246 @@
+//! ## Rules
+//!
+//! Per-subscription auto-download rules, checked against the new videos `update_feed` finds, and
+//! the retention policy that removes what they downloaded once it is no longer wanted.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use regex::Regex;
+
+use crate::downloads::{self, DownloadRequest};
+use crate::library;
+use crate::types::{ChannelDB, Video};
+use crate::youtube::download::{DownloadScope, DownloadType};
+
+#[derive(Clone, PartialEq)]
+pub struct Rule {
+    pub channel_id: String,
+    pub channel_username: String,
+    pub audio_only: bool,
+    /// Minimum length in seconds.
+    pub min_duration: Option<i64>,
+    pub title_pattern: Option<String>,
+    /// Number of the rule's downloads to keep, newest first.
+    pub keep_last: Option<i64>,
+    /// Age in days after which the rule's downloads are deleted.
+    pub keep_days: Option<i64>,
+    pub delete_watched: bool,
+}
+
+impl Rule {
+    fn matches(&self, video: &Video) -> bool {
+        match &self.title_pattern {
+            Some(pattern) => Regex::new(pattern).is_ok_and(|regex| regex.is_match(&video.title)),
+            None => true,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = vec![String::from(match self.audio_only {
+            true => "audio",
+            false => "video",
+        })];
+
+        if let Some(seconds) = self.min_duration {
+            parts.push(format!("longer than {} min", seconds / 60));
+        }
+        if let Some(pattern) = &self.title_pattern {
+            parts.push(format!("title matches /{}/", pattern));
+        }
+        if let Some(count) = self.keep_last {
+            parts.push(format!("keep last {}", count));
+        }
+        if let Some(days) = self.keep_days {
+            parts.push(format!("delete after {} days", days));
+        }
+        if self.delete_watched {
+            parts.push(String::from("delete once watched"));
+        }
+
+        format!("{}: {}", self.channel_username, parts.join(", "))
+    }
+}
+
+/// Finds a subscription by channel id or name, ignoring case.
+pub async fn find_subscription(name: &str) -> Result<ChannelDB, String> {
+    let pool = crate::config::db::get();
+
+    sqlx::query_as!(
+        ChannelDB,
+        r#"
+            SELECT channel_id, channel_username FROM subscriptions
+            WHERE channel_id = ?1 COLLATE NOCASE OR channel_username = ?1 COLLATE NOCASE
+        "#,
+        name
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(|_| String::from("Failed to read subscriptions"))?
+    .ok_or(format!("Not subscribed to {}", name))
+}
+
+pub async fn set_rule(rule: &Rule) -> Result<(), String> {
+    if let Some(pattern) = &rule.title_pattern {
+        Regex::new(pattern).map_err(|e| format!("Invalid title pattern: {}", e))?;
+    }
+
+    let pool = crate::config::db::get();
+    sqlx::query!(
+        r#"
+            INSERT INTO auto_download_rules ( channel_id, audio_only, min_duration, title_pattern, keep_last, keep_days, delete_watched )
+            VALUES ( ?1, ?2, ?3, ?4, ?5, ?6, ?7 )
+            ON CONFLICT(channel_id) DO UPDATE SET
+                audio_only = excluded.audio_only,
+                min_duration = excluded.min_duration,
+                title_pattern = excluded.title_pattern,
+                keep_last = excluded.keep_last,
+                keep_days = excluded.keep_days,
+                delete_watched = excluded.delete_watched
+        "#,
+        rule.channel_id,
+        rule.audio_only,
+        rule.min_duration,
+        rule.title_pattern,
+        rule.keep_last,
+        rule.keep_days,
+        rule.delete_watched
+    )
+    .execute(&pool)
+    .await
+    .map_err(|_| String::from("Failed to save rule"))?;
+
+    Ok(())
+}
+
+pub async fn remove_rule(channel_id: &str) -> Result<(), String> {
+    let pool = crate::config::db::get();
+
+    sqlx::query!(
+        r#"DELETE FROM auto_download_rules WHERE channel_id = ?1"#,
+        channel_id
+    )
+    .execute(&pool)
+    .await
+    .map_err(|_| String::from("Failed to remove rule"))?;
+
+    Ok(())
+}
+
+pub async fn get_rules() -> Result<Vec<Rule>, String> {
+    let pool = crate::config::db::get();
+
+    sqlx::query_as!(
+        Rule,
+        r#"
+            SELECT
+            rules.channel_id,
+            subscriptions.channel_username,
+            rules.audio_only,
+            rules.min_duration,
+            rules.title_pattern,
+            rules.keep_last,
+            rules.keep_days,
+            rules.delete_watched
+            FROM auto_download_rules AS rules
+            JOIN subscriptions ON rules.channel_id = subscriptions.channel_id
+            ORDER BY subscriptions.channel_username;
+        "#
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|_| String::from("Failed to read rules"))
+}
+
+/// Queues the downloads of the new feed videos that match their channel's rule. The minimum
+/// length is left to yt-dlp, which knows it once it fetches the video.
+pub async fn queue_matching(videos: &[Video]) -> Result<(), String> {
+    let rules = get_rules().await?;
+
+    for video in videos {
+        let Some(rule) = rules
+            .iter()
+            .find(|rule| rule.channel_id == video.channel.id)
+        else {
+            continue;
+        };
+        if !rule.matches(video) {
+            continue;
+        }
+
+        downloads::get().enqueue(DownloadRequest {
+            url: video.url.clone(),
+            title: video.title.clone(),
+            channel: Some(video.channel.username.clone()),
+            download_type: match rule.audio_only {
+                true => DownloadType::Audio,
+                false => DownloadType::Video,
+            },
+            scope: DownloadScope::Single,
+            format: None,
+            match_filter: rule
+                .min_duration
+                .map(|seconds| format!("duration >= {}", seconds)),
+            rule_channel_id: Some(rule.channel_id.clone()),
+        });
+    }
+
+    Ok(())
+}
+
+/// Deletes the downloads that the retention policy of their channel no longer keeps, returning
+/// how many were deleted. Only files the channel's rule downloaded are considered, never manual
+/// downloads or clips.
+pub async fn apply_retention() -> Result<usize, String> {
+    let rules = get_rules().await?;
+    let watched = watched_videos().await?;
+    let mut deleted = 0;
+
+    for rule in rules {
+        let expired_before = rule
+            .keep_days
+            .map(|days| Utc::now() - TimeDelta::days(days));
+
+        // Library entries come newest first
+        let entries = library::get_rule_downloads(&rule.channel_id).await?;
+        let expired = entries.iter().enumerate().filter(|(index, entry)| {
+            let beyond_count = rule.keep_last.is_some_and(|keep| *index as i64 >= keep);
+            let too_old = expired_before.is_some_and(|cutoff| {
+                DateTime::parse_from_rfc3339(&entry.downloaded_at)
+                    .is_ok_and(|downloaded_at| downloaded_at < cutoff)
+            });
+            let seen = rule.delete_watched && watched.contains(&entry.video_id);
+
+            beyond_count || too_old || seen
+        });
+
+        // One file that cannot be removed must not keep the others around
+        for (_, entry) in expired {
+            if let Err(e) = library::delete(&entry.path).await {
+                log::warn!("Retention could not delete {}: {}", entry.path, e);
+                continue;
+            }
+            deleted += 1;
+        }
+    }
+
+    Ok(deleted)
+}
+
+/// Ids of the videos marked watched in the feed or played to the end.
+async fn watched_videos() -> Result<HashSet<String>, String> {
+    let pool = crate::config::db::get();
+
+    let ids = sqlx::query_scalar!(
+        r#"
+            SELECT id AS "id!" FROM feed WHERE watched
+            UNION
+            SELECT video_id FROM history WHERE completed
+        "#
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|_| String::from("Failed to read watched videos"))?;
+
+    Ok(ids.into_iter().collect())
+}
@@ -0,0 +1,318 @@
+//! ## SponsorBlock
+//!
+//! Client for the SponsorBlock API. Segments are looked up by a prefix of the hashed video id, so
+//! the server never learns which video is being watched, and cached in SQLite.
+
+use std::str::FromStr;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::config;
+
+pub const DEFAULT_API_URL: &str = "https://sponsor.ajay.app";
+pub const DEFAULT_CATEGORIES: &str = "sponsor=skip,intro=skip,selfpromo=skip";
+
+/// How long fetched segments are trusted before asking the server again.
+const CACHE_TTL: TimeDelta = TimeDelta::days(1);
+
+/// Length of the hashed video id prefix sent to the server.
+const HASH_PREFIX_LEN: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SegmentAction {
+    Skip,
+    Mute,
+    Ignore,
+}
+
+impl FromStr for SegmentAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(SegmentAction::Skip),
+            "mute" => Ok(SegmentAction::Mute),
+            "ignore" => Ok(SegmentAction::Ignore),
+            s => Err(format!(
+                "Unknown SponsorBlock action \"{}\" (expected skip, mute or ignore)",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Segment {
+    pub category: String,
+    pub start: f64,
+    pub end: f64,
+    pub action: SegmentAction,
+}
+
+impl Segment {
+    pub fn contains(&self, position: f64) -> bool {
+        position >= self.start && position < self.end
+    }
+}
+
+/// Parses category settings such as `sponsor=skip,intro=mute,selfpromo=ignore`.
+pub fn parse_categories(settings: &str) -> Result<Vec<(String, SegmentAction)>, String> {
+    settings
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (category, action) = entry.split_once('=').ok_or(format!(
+                "Invalid SponsorBlock setting \"{}\" (expected category=action)",
+                entry
+            ))?;
+            Ok((category.trim().to_string(), action.trim().parse()?))
+        })
+        .collect()
+}
+
+/// Segments of `video_id` to act on during playback, according to the configured categories.
+pub async fn segments(video_id: &str) -> Result<Vec<Segment>, String> {
+    let network = &config::get().network;
+    let categories = parse_categories(&network.sponsorblock_categories)?
+        .into_iter()
+        .filter(|(_, action)| *action != SegmentAction::Ignore)
+        .collect::<Vec<_>>();
+
+    if categories.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut names = categories
+        .iter()
+        .map(|(c, _)| c.as_str())
+        .collect::<Vec<_>>();
+    names.sort_unstable();
+    names.dedup();
+
+    let cached = match cached_segments(video_id, &names).await? {
+        Some(cached) => cached,
+        None => {
+            let fetched = fetch_segments(&network.sponsorblock_url, video_id, &names).await?;
+            store_segments(video_id, &names, &fetched).await?;
+            fetched
+        }
+    };
+
+    let segments = cached
+        .into_iter()
+        .filter_map(|(category, start, end)| {
+            categories
+                .iter()
+                .find(|(c, _)| *c == category)
+                .map(|(_, action)| Segment {
+                    category,
+                    start,
+                    end,
+                    action: *action,
+                })
+        })
+        .collect();
+
+    Ok(segments)
+}
+
+/// Segments stored for `video_id`, or `None` when they were never fetched, are stale or were
+/// fetched without some of `categories`.
+async fn cached_segments(
+    video_id: &str,
+    categories: &[&str],
+) -> Result<Option<Vec<(String, f64, f64)>>, String> {
+    let pool = crate::config::db::get();
+
+    let cache = sqlx::query!(
+        r#"SELECT fetched_at, categories FROM sponsorblock_cache WHERE video_id = ?1"#,
+        video_id
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(|_| String::from("Failed to read SponsorBlock cache"))?;
+
+    let fresh = cache.is_some_and(|cache| {
+        let cached_categories = cache.categories.split(',').collect::<Vec<_>>();
+        cache
+            .fetched_at
+            .parse::<DateTime<Utc>>()
+            .is_ok_and(|fetched_at| Utc::now() - fetched_at < CACHE_TTL)
+            && categories
+                .iter()
+                .all(|category| cached_categories.contains(category))
+    });
+
+    if !fresh {
+        return Ok(None);
+    }
+
+    let segments = sqlx::query!(
+        r#"SELECT category, start_time, end_time FROM sponsorblock_segments WHERE video_id = ?1"#,
+        video_id
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|_| String::from("Failed to read SponsorBlock cache"))?
+    .into_iter()
+    .map(|row| (row.category, row.start_time, row.end_time))
+    .collect();
+
+    Ok(Some(segments))
+}
+
+async fn store_segments(
+    video_id: &str,
+    categories: &[&str],
+    segments: &[(String, f64, f64)],
+) -> Result<(), String> {
+    let pool = crate::config::db::get();
+    let fetched_at = Utc::now().to_rfc3339();
+    let categories = categories.join(",");
+    let error = |_| String::from("Failed to write SponsorBlock cache");
+
+    let mut transaction = pool.begin().await.map_err(error)?;
+
+    sqlx::query!(
+        r#"DELETE FROM sponsorblock_segments WHERE video_id = ?1"#,
+        video_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(error)?;
+
+    sqlx::query!(
+        r#"
+            INSERT INTO sponsorblock_cache ( video_id, fetched_at, categories ) VALUES ( ?1, ?2, ?3 )
+            ON CONFLICT(video_id) DO UPDATE SET
+                fetched_at = excluded.fetched_at,
+                categories = excluded.categories
+        "#,
+        video_id,
+        fetched_at,
+        categories
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(error)?;
+
+    for (category, start, end) in segments {
+        sqlx::query!(
+            r#"
+                INSERT INTO sponsorblock_segments ( video_id, category, start_time, end_time )
+                VALUES ( ?1, ?2, ?3, ?4 )
+            "#,
+            video_id,
+            category,
+            start,
+            end
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(error)?;
+    }
+
+    transaction.commit().await.map_err(error)
+}
+
+fn hash_prefix(video_id: &str) -> String {
+    let hash = Sha256::digest(video_id.as_bytes());
+    hash.iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>()[..HASH_PREFIX_LEN]
+        .to_string()
+}
+
+/// Asks the server for the segments of every video sharing the hash prefix of `video_id` and
+/// keeps those of `video_id`.
+async fn fetch_segments(
+    api_url: &str,
+    video_id: &str,
+    categories: &[&str],
+) -> Result<Vec<(String, f64, f64)>, String> {
+    let url = format!(
+        "{}/api/skipSegments/{}",
+        api_url.trim_end_matches('/'),
+        hash_prefix(video_id)
+    );
+    let categories = serde_json::to_string(categories)
+        .map_err(|_| String::from("Failed to encode SponsorBlock categories"))?;
+
+    let res = reqwest::Client::new()
+        .get(url)
+        .query(&[("categories", categories)])
+        .send()
+        .await
+        .map_err(|_| String::from("Failed on SponsorBlock request"))?;
+
+    // The server answers 404 when no video with this prefix has segments.
+    if res.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(Vec::new());
+    }
+
+    let body = res
+        .error_for_status()
+        .map_err(|e| format!("SponsorBlock request failed: {}", e))?
+        .text()
+        .await
+        .map_err(|_| String::from("Failed on SponsorBlock request"))?;
+
+    let json: Value = serde_json::from_str(&body)
+        .map_err(|_| String::from("Failed to parse SponsorBlock response"))?;
+
+    let segments = json
+        .as_array()
+        .ok_or(String::from("Unexpected SponsorBlock response"))?
+        .iter()
+        .filter(|video| video["videoID"].as_str() == Some(video_id))
+        .flat_map(|video| video["segments"].as_array().cloned().unwrap_or_default())
+        .filter_map(|segment| {
+            Some((
+                segment["category"].as_str()?.to_string(),
+                segment["segment"][0].as_f64()?,
+                segment["segment"][1].as_f64()?,
+            ))
+        })
+        .collect();
+
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_categories_and_actions() {
+        let categories =
+            parse_categories(" sponsor=skip, intro = mute,,selfpromo=ignore ").unwrap();
+        assert_eq!(
+            categories,
+            [
+                (String::from("sponsor"), SegmentAction::Skip),
+                (String::from("intro"), SegmentAction::Mute),
+                (String::from("selfpromo"), SegmentAction::Ignore),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_settings_have_no_categories() {
+        assert!(parse_categories("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_entries_without_an_action() {
+        let error = parse_categories("sponsor=skip,intro").unwrap_err();
+        assert!(error.contains("\"intro\""));
+    }
+
+    #[test]
+    fn rejects_unknown_actions() {
+        let error = parse_categories("sponsor=hide").unwrap_err();
+        assert!(error.contains("\"hide\""));
+    }
+}
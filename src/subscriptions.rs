@@ -0,0 +1,145 @@
+//! ## Subscriptions
+//!
+//! Subscriptions as the Subscriptions view lists them: with their latest upload, how many of
+//! their feed videos are unwatched, their groups and how their last feed refresh went.
+
+use std::cmp::Reverse;
+
+use chrono::{DateTime, Local};
+
+#[derive(Clone, PartialEq)]
+pub struct Subscription {
+    pub channel_id: String,
+    /// Name shown in silk, which can differ from the one on YouTube.
+    pub channel_username: String,
+    /// Publication date of the newest video of the channel in the feed.
+    pub last_upload: Option<String>,
+    pub unwatched: i64,
+    /// Names of the groups the channel is in, separated by commas.
+    pub groups: Option<String>,
+    pub refreshed_at: Option<String>,
+    pub refresh_error: Option<String>,
+}
+
+impl Subscription {
+    pub fn describe(&self) -> String {
+        let date = |timestamp: &Option<String>, format: &str| {
+            timestamp
+                .as_deref()
+                .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+                .map(|date| date.with_timezone(&Local).format(format).to_string())
+        };
+
+        let mut parts = vec![self.channel_username.clone()];
+        parts.push(match date(&self.last_upload, "%Y-%m-%d") {
+            Some(date) => format!("last upload {}", date),
+            None => String::from("no recent uploads"),
+        });
+        parts.push(format!("{} unwatched", self.unwatched));
+        if let Some(groups) = &self.groups {
+            parts.push(format!("[{}]", groups));
+        }
+        parts.push(
+            match (
+                &self.refresh_error,
+                date(&self.refreshed_at, "%Y-%m-%d %H:%M"),
+            ) {
+                (Some(error), _) => format!("refresh failed: {}", error),
+                (None, Some(date)) => format!("refreshed {}", date),
+                (None, None) => String::from("never refreshed"),
+            },
+        );
+
+        parts.join(" · ")
+    }
+}
+
+/// Order of the Subscriptions view.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum SubscriptionSort {
+    #[default]
+    Name,
+    LastUpload,
+    Unwatched,
+}
+
+impl SubscriptionSort {
+    pub fn next(self) -> Self {
+        match self {
+            SubscriptionSort::Name => SubscriptionSort::LastUpload,
+            SubscriptionSort::LastUpload => SubscriptionSort::Unwatched,
+            SubscriptionSort::Unwatched => SubscriptionSort::Name,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SubscriptionSort::Name => "by name",
+            SubscriptionSort::LastUpload => "by last upload",
+            SubscriptionSort::Unwatched => "by unwatched videos",
+        }
+    }
+}
+
+pub async fn get_subscriptions(sort: SubscriptionSort) -> Result<Vec<Subscription>, String> {
+    let pool = crate::config::db::get();
+
+    let mut subscriptions = sqlx::query_as!(
+        Subscription,
+        r#"
+            SELECT
+            subscriptions.channel_id,
+            subscriptions.channel_username,
+            (SELECT MAX(published_at) FROM feed WHERE feed.channel = subscriptions.channel_id)
+                AS "last_upload?: String",
+            (SELECT COUNT(*) FROM feed WHERE feed.channel = subscriptions.channel_id AND NOT feed.watched)
+                AS "unwatched!: i64",
+            (
+                SELECT GROUP_CONCAT(subscription_groups.name, ', ')
+                FROM subscription_group_members AS members
+                JOIN subscription_groups ON members.group_id = subscription_groups.id
+                WHERE members.channel_id = subscriptions.channel_id
+            ) AS "groups?: String",
+            subscriptions.refreshed_at,
+            subscriptions.refresh_error
+            FROM subscriptions
+            ORDER BY subscriptions.channel_username COLLATE NOCASE
+        "#
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|_| String::from("Failed to read subscriptions"))?;
+
+    // Stable, so ties stay in name order; RFC 3339 dates in UTC sort as text
+    match sort {
+        SubscriptionSort::Name => {}
+        SubscriptionSort::LastUpload => {
+            subscriptions.sort_by(|a, b| b.last_upload.cmp(&a.last_upload))
+        }
+        SubscriptionSort::Unwatched => {
+            subscriptions.sort_by_key(|subscription| Reverse(subscription.unwatched))
+        }
+    }
+
+    Ok(subscriptions)
+}
+
+/// Changes the name silk shows for a subscription.
+pub async fn rename(channel_id: &str, name: &str) -> Result<(), String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(String::from("A subscription needs a name"));
+    }
+
+    let pool = crate::config::db::get();
+    sqlx::query!(
+        r#"UPDATE subscriptions SET channel_username = ?2 WHERE channel_id = ?1"#,
+        channel_id,
+        name
+    )
+    .execute(&pool)
+    .await
+    .map_err(|_| String::from("Failed to rename subscription"))?;
+
+    Ok(())
+}
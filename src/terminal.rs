@@ -1,4 +1,5 @@
 use std::{
+    error::Error,
     io::{self, Stdout},
     process::{self},
 };
@@ -22,10 +23,16 @@ pub fn init() -> Result<Terminal, io::Error> {
     Ok(terminal)
 }
 
-pub fn exit(terminal: &mut Terminal) -> Result<(), io::Error> {
+/// Restores the terminal and exits, reporting `result` once the terminal is usable again.
+pub fn exit(terminal: &mut Terminal, result: Result<(), Box<dyn Error>>) -> Result<(), io::Error> {
     disable_raw_mode()?;
     execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
     terminal.show_cursor()?;
 
+    if let Err(e) = result {
+        eprintln!("{e}");
+        process::exit(1);
+    }
+
     process::exit(0);
 }
@@ -2,27 +2,93 @@
 //!
 //! app model
 
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use tokio::sync::mpsc;
 use tuirealm::event::NoUserEvent;
-use tuirealm::props::{Borders, Color};
-use tuirealm::ratatui::layout::{Constraint, Direction, Layout};
+use tuirealm::props::{Alignment, Borders};
+use tuirealm::ratatui::layout::{Constraint, Direction, Flex, Layout, Rect};
+use tuirealm::ratatui::widgets::Clear;
 use tuirealm::terminal::{CrosstermTerminalAdapter, TerminalAdapter, TerminalBridge};
-use tuirealm::{Application, EventListenerCfg, Update};
+use tuirealm::{Application, AttrValue, Attribute, EventListenerCfg, State, StateValue, Update};
 
-use crate::types::ContentItem;
-use crate::youtube::{get_feed_videos, search_content, update_feed};
+use crate::config::{self, PlaybackMode};
+use crate::downloads::{self, Job, JobId, JobStatus, progress::format_bytes};
+use crate::groups::{self, Group};
+use crate::history::{self, format_position};
+use crate::library;
+use crate::local_search::{self, LocalMatch};
+use crate::player;
+use crate::subscriptions::{self, Subscription, SubscriptionSort, get_subscriptions};
+use crate::types::{Channel, ContentItem, HistoryDB, LibraryDB, Video};
+use crate::youtube::download::{DownloadScope, DownloadType};
+use crate::youtube::formats::{Format, list_formats};
+use crate::youtube::parser::parse_channel_videos;
+use crate::youtube::{
+    FeedFilter, Unsubscribed, get_feed_videos, mark_all_watched, refresh_subscription,
+    search_content, set_watched, update_feed,
+};
 
-use super::super::components::{Input, Menu};
+use super::super::components::{Input, Label, Menu};
 use super::super::tui::{Id, Msg};
 
 pub enum ActiveView {
     SearchResult,
+    LocalSearch,
+    Feed,
+    Downloads,
+    History,
+    Library,
+    Subscriptions,
     MainMenu,
     Idle,
 }
 
+/// What a submitted `Input` is searching.
+pub enum SearchTarget {
+    Youtube,
+    /// Feed, history and library, offline.
+    Local,
+    History,
+    Library,
+    /// Which uploads of the channel to download.
+    ChannelDownload(Channel, DownloadType),
+    /// Which part of the video to download.
+    Clip(ContentItem, DownloadType),
+    /// New name of the subscription.
+    Rename(Channel),
+    /// Groups of the subscription, starting from the current ones.
+    Groups(Channel, String),
+    /// Confirmation before the whole watch history is cleared.
+    ClearHistory,
+}
+
+const YOUTUBE_LABEL: &str = "Search on Youtube (Tab: offline)";
+
+/// Centered rectangle taking the given percentages of `area`.
+fn popup_area(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let [area] = Layout::vertical([Constraint::Percentage(percent_y)])
+        .flex(Flex::Center)
+        .areas(area);
+    let [area] = Layout::horizontal([Constraint::Percentage(percent_x)])
+        .flex(Flex::Center)
+        .areas(area);
+    area
+}
+
+fn main_menu() -> Menu {
+    Menu::new(vec![
+        "Search".to_string(),
+        "Feed".to_string(),
+        "Subscriptions".to_string(),
+        "History".to_string(),
+        "Downloads".to_string(),
+        "Library".to_string(),
+        "Exit".to_string(),
+    ])
+}
+
 pub struct Model<T>
 where
     T: TerminalAdapter,
@@ -32,7 +98,25 @@ where
     pub redraw: bool,
     pub terminal: TerminalBridge<T>,
     pub search_result: Vec<ContentItem>,
+    pub local_results: Vec<LocalMatch>,
+    pub history: Vec<HistoryDB>,
+    /// Search the History view is filtered by.
+    pub history_query: String,
+    pub downloads: Vec<Job>,
+    pub library: Vec<LibraryDB>,
+    pub subscriptions: Vec<Subscription>,
+    pub subscription_sort: SubscriptionSort,
+    pub feed_filter: FeedFilter,
+    /// Group the Feed view shows, all subscriptions when `None`.
+    pub feed_group: Option<Group>,
+    /// Item whose formats are being picked, with the formats once they are listed.
+    pub format_picker: Option<(ContentItem, Vec<Format>)>,
+    /// Titles of what is currently playing.
+    pub playing: Vec<String>,
+    /// Last unsubscribe, and until when it can be undone.
+    pub undo: Option<(Unsubscribed, Instant)>,
     pub active_view: ActiveView,
+    pub search_target: SearchTarget,
     pub tx: mpsc::Sender<Msg>,
 }
 
@@ -45,7 +129,20 @@ impl Default for Model<CrosstermTerminalAdapter> {
             redraw: true,
             terminal: TerminalBridge::init_crossterm().expect("Cannot initialize terminal"),
             search_result: Vec::default(),
+            local_results: Vec::default(),
+            history: Vec::default(),
+            history_query: String::new(),
+            downloads: Vec::default(),
+            library: Vec::default(),
+            subscriptions: Vec::default(),
+            subscription_sort: SubscriptionSort::default(),
+            feed_filter: FeedFilter::default(),
+            feed_group: None,
+            format_picker: None,
+            playing: Vec::new(),
+            undo: None,
             active_view: ActiveView::MainMenu,
+            search_target: SearchTarget::Youtube,
             tx,
         }
     }
@@ -62,11 +159,22 @@ where
                     let chunks = Layout::default()
                         .direction(Direction::Vertical)
                         .margin(1)
-                        .constraints([Constraint::Length(3), Constraint::Min(1)])
+                        .constraints([
+                            Constraint::Length(3),
+                            Constraint::Min(1),
+                            Constraint::Length(1),
+                        ])
                         .split(f.area());
 
                     self.app.view(&Id::Input, f, chunks[0]);
                     self.app.view(&Id::Menu, f, chunks[1]);
+                    self.app.view(&Id::Label, f, chunks[2]);
+
+                    if self.app.mounted(&Id::FormatPicker) {
+                        let popup = popup_area(chunks[1], 90, 70);
+                        f.render_widget(Clear, popup);
+                        self.app.view(&Id::FormatPicker, f, popup);
+                    }
                 })
                 .is_ok()
         );
@@ -86,22 +194,23 @@ where
                 Box::new(
                     Input::default()
                         .borders(Borders::default())
-                        .foreground(Color::Green)
-                        .label("Search on Youtube")
+                        .foreground(config::get().ui.search)
+                        .label(YOUTUBE_LABEL)
                 ),
                 Vec::default()
             )
             .is_ok()
         );
 
+        assert!(
+            app.mount(Id::Menu, Box::new(main_menu()), Vec::default())
+                .is_ok()
+        );
+
         assert!(
             app.mount(
-                Id::Menu,
-                Box::new(Menu::new(vec![
-                    "Search".to_string(),
-                    "Feed".to_string(),
-                    "Exit".to_string()
-                ])),
+                Id::Label,
+                Box::new(Label::default().foreground(config::get().ui.status)),
                 Vec::default()
             )
             .is_ok()
@@ -113,22 +222,287 @@ where
     }
 
     fn go_to_main_menu(&mut self) {
+        assert!(
+            self.app
+                .remount(Id::Menu, Box::new(main_menu()), Vec::default())
+                .is_ok()
+        );
+        self.active_view = ActiveView::MainMenu;
+        self.focus_search(SearchTarget::Youtube);
+        assert!(self.app.active(&Id::Menu).is_ok());
+    }
+
+    /// Points the `Input` at `target` and updates its label to match. Prompts also get their
+    /// starting text, while searches keep what was typed in an earlier search.
+    fn focus_search(&mut self, target: SearchTarget) {
+        let after_prompt = !matches!(
+            self.search_target,
+            SearchTarget::Youtube
+                | SearchTarget::Local
+                | SearchTarget::History
+                | SearchTarget::Library
+        );
+        let text = match &target {
+            SearchTarget::Youtube
+            | SearchTarget::Local
+            | SearchTarget::History
+            | SearchTarget::Library => after_prompt.then(String::new),
+            SearchTarget::ChannelDownload(..)
+            | SearchTarget::Clip(..)
+            | SearchTarget::ClearHistory => Some(String::new()),
+            SearchTarget::Rename(channel) => Some(channel.username.clone()),
+            SearchTarget::Groups(_, current) => Some(current.clone()),
+        };
+        let label = match &target {
+            SearchTarget::Youtube => String::from(YOUTUBE_LABEL),
+            SearchTarget::Local => {
+                String::from("Search feed, history and library offline (Tab: Youtube)")
+            }
+            SearchTarget::History => String::from("Search history"),
+            SearchTarget::Library => String::from("Search library"),
+            SearchTarget::ChannelDownload(channel, _) => format!(
+                "Download from {}: number of latest uploads, or YYYYMMDD-YYYYMMDD",
+                channel.username
+            ),
+            SearchTarget::Clip(content_item, download_type) => format!(
+                "{} clip of {}: start-end such as 1:00-3:00, or a chapter title",
+                match download_type {
+                    DownloadType::Video => "Video",
+                    DownloadType::Audio => "Audio",
+                },
+                content_item.title()
+            ),
+            SearchTarget::Rename(channel) => format!("New name of {}", channel.username),
+            SearchTarget::Groups(channel, _) => {
+                format!("Groups of {}, separated by commas", channel.username)
+            }
+            SearchTarget::ClearHistory => String::from(
+                "Clear the whole history, resume positions included? Type yes to confirm",
+            ),
+        };
+        assert!(
+            self.app
+                .attr(
+                    &Id::Input,
+                    Attribute::Title,
+                    AttrValue::Title((label, Alignment::Left)),
+                )
+                .is_ok()
+        );
+        if let Some(text) = text {
+            assert!(
+                self.app
+                    .attr(&Id::Input, Attribute::Value, AttrValue::String(text))
+                    .is_ok()
+            );
+        }
+        self.search_target = target;
+    }
+
+    fn close_format_picker(&mut self) {
+        self.format_picker = None;
+        if self.app.mounted(&Id::FormatPicker) {
+            assert!(self.app.umount(&Id::FormatPicker).is_ok());
+        }
+        assert!(self.app.active(&Id::Menu).is_ok());
+    }
+
+    /// Item and format selector chosen in the format picker, closing it.
+    fn picked_format(&mut self, idx: usize) -> Option<(ContentItem, String)> {
+        let (content_item, formats) = self.format_picker.take()?;
+        self.close_format_picker();
+        formats
+            .get(idx)
+            .map(|format| (content_item, format.selector()))
+    }
+
+    fn set_status(&mut self, text: String) {
+        assert!(
+            self.app
+                .attr(&Id::Label, Attribute::Text, AttrValue::String(text))
+                .is_ok()
+        );
+    }
+
+    fn show_playing(&mut self) {
+        let status = match self.playing.as_slice() {
+            [] => String::new(),
+            [title] => format!("▶ Playing: {}", title),
+            [title, rest @ ..] => format!("▶ Playing: {} (+{} more)", title, rest.len()),
+        };
+        self.set_status(status);
+    }
+
+    /// Starts playback in the background. The player runs as a child process supervised by a
+    /// task, which reports back with `Msg::PlaybackEnded` once it exits.
+    fn play(&mut self, mut content_item: ContentItem, mode: PlaybackMode, format: Option<String>) {
+        let title = content_item.title().to_string();
+        self.playing.push(title.clone());
+        self.show_playing();
+
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            let status = tx.clone();
+            let notify = move |message| {
+                status.try_send(Msg::Status(message)).ok();
+            };
+            let result = content_item.play(mode, format, notify).await;
+            tx.send(Msg::PlaybackEnded(title, result)).await.ok();
+        });
+    }
+
+    /// Plays the file of a library entry, like `play`, rather than any other copy of the video.
+    fn play_file(&mut self, entry: LibraryDB, mode: PlaybackMode) {
+        let title = entry.title.clone();
+        self.playing.push(title.clone());
+        self.show_playing();
+
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            let status = tx.clone();
+            let notify = move |message| {
+                status.try_send(Msg::Status(message)).ok();
+            };
+            let path = PathBuf::from(&entry.path);
+            let result = player::play_file(&Video::from(entry), &path, mode, notify).await;
+            tx.send(Msg::PlaybackEnded(title, result)).await.ok();
+        });
+    }
+
+    /// Content item at `idx` in the list currently on screen.
+    fn selected_item(&self, idx: usize) -> Option<ContentItem> {
+        match self.active_view {
+            ActiveView::SearchResult | ActiveView::Feed => self.search_result.get(idx).cloned(),
+            ActiveView::LocalSearch => self
+                .local_results
+                .get(idx)
+                .map(|entry| ContentItem::Video(Video::from(entry.clone()))),
+            ActiveView::History => self
+                .history
+                .get(idx)
+                .map(|entry| ContentItem::Video(Video::from(entry.clone()))),
+            ActiveView::Library => self
+                .library
+                .get(idx)
+                .map(|entry| ContentItem::Video(Video::from(entry.clone()))),
+            ActiveView::Subscriptions => self.subscriptions.get(idx).map(|subscription| {
+                ContentItem::Channel(Channel::new(
+                    &subscription.channel_id,
+                    &subscription.channel_username,
+                ))
+            }),
+            ActiveView::Downloads | ActiveView::MainMenu | ActiveView::Idle => None,
+        }
+    }
+
+    /// Index of the selected item of the main list.
+    fn menu_selection(&self) -> usize {
+        match self.app.state(&Id::Menu) {
+            Ok(State::One(StateValue::Usize(index))) => index,
+            _ => 0,
+        }
+    }
+
+    fn show_downloads(&mut self, keep_selection: bool) {
+        let selected = match keep_selection {
+            true => self.menu_selection(),
+            false => 0,
+        };
+        let menu_items = self.downloads.iter().map(Job::describe).collect();
         assert!(
             self.app
                 .remount(
                     Id::Menu,
-                    Box::new(Menu::new(vec![
-                        "Search".to_string(),
-                        "Feed".to_string(),
-                        "Exit".to_string()
-                    ])),
+                    Box::new(Menu::new(menu_items).title("Downloads").selected(selected)),
                     Vec::default()
                 )
                 .is_ok()
         );
-        self.active_view = ActiveView::MainMenu;
+        self.active_view = ActiveView::Downloads;
         assert!(self.app.active(&Id::Menu).is_ok());
     }
+
+    /// Applies `action` to the selected job of the Downloads view, reporting a refusal.
+    fn control_download(&mut self, idx: usize, action: impl FnOnce(JobId) -> Result<(), String>) {
+        if let (ActiveView::Downloads, Some(job)) = (&self.active_view, self.downloads.get(idx))
+            && let Err(e) = action(job.id)
+        {
+            self.set_status(e);
+        }
+    }
+
+    fn download(&mut self, content_item: ContentItem, video_track: bool, format: Option<String>) {
+        let status = match content_item.download(video_track, format) {
+            Ok(()) => format!("Queued download of \"{}\"", content_item.title()),
+            Err(e) => e,
+        };
+        self.set_status(status);
+    }
+
+    fn load_feed(&self) {
+        self.load_feed_after(async { Ok(()) });
+    }
+
+    /// Runs `task` and then reloads the feed so the view reflects what it changed. Errors of
+    /// either end up in the status bar.
+    fn load_feed_after<F>(&self, task: F)
+    where
+        F: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        let tx = self.tx.clone();
+        let filter = self.feed_filter;
+        tokio::spawn(async move {
+            if let Err(e) = task.await {
+                tx.send(Msg::Status(e)).await.ok();
+            }
+            match get_feed_videos(filter).await {
+                Ok(content) => tx.send(Msg::FeedResults(content)).await.ok(),
+                Err(e) => tx.send(Msg::Status(e)).await.ok(),
+            };
+        });
+    }
+
+    fn load_subscriptions(&self) {
+        self.load_subscriptions_after(async { Ok(()) });
+    }
+
+    /// Runs `task` and then reloads the subscriptions, like `load_feed_after` does the feed.
+    fn load_subscriptions_after<F>(&self, task: F)
+    where
+        F: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        let tx = self.tx.clone();
+        let sort = self.subscription_sort;
+        tokio::spawn(async move {
+            if let Err(e) = task.await {
+                tx.send(Msg::Status(e)).await.ok();
+            }
+            match get_subscriptions(sort).await {
+                Ok(subscriptions) => tx.send(Msg::SubscriptionResults(subscriptions)).await.ok(),
+                Err(e) => tx.send(Msg::Status(e)).await.ok(),
+            };
+        });
+    }
+
+    fn load_library(&self, query: String) {
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            match library::get_library(&query).await {
+                Ok(entries) => tx.send(Msg::LibraryResults(entries)).await.ok(),
+                Err(e) => tx.send(Msg::Status(e)).await.ok(),
+            };
+        });
+    }
+
+    fn load_history(&mut self, query: String) {
+        self.history_query = query.clone();
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            if let Ok(entries) = history::get_history(&query).await {
+                tx.send(Msg::HistoryResults(entries)).await.ok();
+            }
+        });
+    }
 }
 
 impl<T> Update<Msg> for Model<T>
@@ -143,6 +517,75 @@ where
                 Msg::AppClose => {
                     self.quit = true;
                 }
+                Msg::MenuSelected(_, idx) if self.app.mounted(&Id::FormatPicker) => {
+                    if let Some((content_item, format)) = self.picked_format(idx) {
+                        self.play(content_item, PlaybackMode::Video, Some(format));
+                    }
+                }
+                Msg::Download(_, idx, video_track) if self.app.mounted(&Id::FormatPicker) => {
+                    if let Some((content_item, format)) = self.picked_format(idx) {
+                        self.download(content_item, video_track, Some(format));
+                    }
+                }
+                Msg::Exit if self.app.mounted(&Id::FormatPicker) => self.close_format_picker(),
+                // The picker is a menu too; its other keys must not act on the list underneath
+                Msg::PlayAudio(..)
+                | Msg::PauseDownload(..)
+                | Msg::CancelDownload(..)
+                | Msg::RetryDownload(..)
+                | Msg::PickFormat(..)
+                | Msg::Subscribe(..)
+                | Msg::Unsubscribe(..)
+                | Msg::Clip(..)
+                | Msg::Delete(..)
+                | Msg::ClearAll
+                | Msg::FocusSearch
+                | Msg::ToggleWatched(..)
+                | Msg::MarkChannelWatched(..)
+                | Msg::MarkAllWatched
+                | Msg::ToggleHideWatched
+                | Msg::ToggleUnwatchedFirst
+                | Msg::NextGroup
+                | Msg::EditGroups(..)
+                | Msg::RenameSubscription(..)
+                | Msg::RefreshSubscription(..)
+                | Msg::CycleSort
+                    if self.app.mounted(&Id::FormatPicker) => {}
+                Msg::PickFormat(_, idx) => {
+                    if let Some(ContentItem::Video(video)) = self.selected_item(idx) {
+                        let url = video.url.clone();
+                        self.format_picker = Some((ContentItem::Video(video), Vec::new()));
+                        self.set_status(String::from("Listing formats..."));
+                        let tx = self.tx.clone();
+                        tokio::spawn(async move {
+                            let formats = list_formats(&url).await;
+                            tx.send(Msg::FormatResults(formats)).await.ok();
+                        });
+                    }
+                }
+                Msg::FormatResults(Err(e)) => {
+                    self.format_picker = None;
+                    self.set_status(e);
+                }
+                Msg::FormatResults(Ok(formats)) => {
+                    self.set_status(String::new());
+                    if let Some((_, picker_formats)) = &mut self.format_picker {
+                        let menu_items = formats.iter().map(Format::describe).collect();
+                        *picker_formats = formats;
+                        assert!(
+                            self.app
+                                .remount(
+                                    Id::FormatPicker,
+                                    Box::new(Menu::new(menu_items).title(
+                                        "Formats (Enter: play, d/m: download, Esc: cancel)"
+                                    )),
+                                    Vec::default()
+                                )
+                                .is_ok()
+                        );
+                        assert!(self.app.active(&Id::FormatPicker).is_ok());
+                    }
+                }
                 Msg::MenuSelected(item, idx) => match self.active_view {
                     ActiveView::MainMenu => match item.as_str() {
                         "Exit" => return Some(Msg::AppClose),
@@ -150,56 +593,466 @@ where
                             assert!(self.app.active(&Id::Input).is_ok());
                             self.active_view = ActiveView::Idle;
                         }
+                        "Downloads" => {
+                            self.downloads = downloads::get().jobs();
+                            self.show_downloads(false);
+                        }
+                        "Library" => {
+                            self.load_library(String::new());
+                            self.active_view = ActiveView::Idle;
+                        }
+                        "History" => {
+                            self.load_history(String::new());
+                            self.active_view = ActiveView::Idle;
+                        }
                         "Feed" => {
-                            let tx = self.tx.clone();
-                            tokio::spawn(async move {
-                                let _ = update_feed().await;
-                                if let Ok(content) = get_feed_videos().await {
-                                    tx.send(Msg::SearchResults(content)).await.ok();
-                                }
-                            });
+                            self.load_feed_after(update_feed());
                             self.active_view = ActiveView::Idle;
                             assert!(self.app.active(&Id::Menu).is_ok());
                         }
+                        "Subscriptions" => {
+                            self.load_subscriptions();
+                            self.active_view = ActiveView::Idle;
+                        }
                         _ => {}
                     },
-                    ActiveView::SearchResult => {
-                        let mut content_item = self.search_result[idx].clone();
-                        tokio::spawn(async move {
-                            content_item.play().await;
-                        });
+                    ActiveView::Library => {
+                        if let Some(entry) = self.library.get(idx).cloned() {
+                            self.play_file(entry, PlaybackMode::Video);
+                        }
+                    }
+                    ActiveView::SearchResult
+                    | ActiveView::LocalSearch
+                    | ActiveView::Feed
+                    | ActiveView::History => {
+                        if let Some(content_item) = self.selected_item(idx) {
+                            self.play(content_item, PlaybackMode::Video, None);
+                        }
+                    }
+                    ActiveView::Subscriptions => {
+                        if let Some(ContentItem::Channel(channel)) = self.selected_item(idx) {
+                            self.set_status(format!("Loading uploads of {}...", channel.username));
+                            let tx = self.tx.clone();
+                            tokio::spawn(async move {
+                                match parse_channel_videos(channel).await {
+                                    Ok(videos) => {
+                                        let content =
+                                            videos.into_iter().map(ContentItem::Video).collect();
+                                        tx.send(Msg::Status(String::new())).await.ok();
+                                        tx.send(Msg::SearchResults(content)).await.ok()
+                                    }
+                                    Err(e) => tx.send(Msg::Status(e)).await.ok(),
+                                };
+                            });
+                        }
+                    }
+                    ActiveView::Downloads => {
+                        if let Some(job) = self.downloads.get(idx) {
+                            let status = match &job.status {
+                                JobStatus::Failed(error) => {
+                                    error.lines().last().unwrap_or_default().to_string()
+                                }
+                                _ => job.request.url.clone(),
+                            };
+                            self.set_status(status);
+                        }
                     }
                     ActiveView::Idle => {}
                 },
 
+                Msg::PlayAudio(_, idx) if matches!(self.active_view, ActiveView::Library) => {
+                    if let Some(entry) = self.library.get(idx).cloned() {
+                        self.play_file(entry, PlaybackMode::Audio);
+                    }
+                }
+                Msg::PlayAudio(_, idx) => {
+                    if let Some(content_item) = self.selected_item(idx) {
+                        self.play(content_item, PlaybackMode::Audio, None);
+                    }
+                }
+                Msg::PlaybackEnded(title, result) => {
+                    if let Some(pos) = self.playing.iter().position(|t| *t == title) {
+                        self.playing.remove(pos);
+                    }
+                    match result {
+                        Ok(()) => self.show_playing(),
+                        Err(e) => {
+                            self.set_status(format!("Playback of \"{}\" failed: {}", title, e))
+                        }
+                    }
+                }
+                Msg::Status(text) => self.set_status(text),
+                Msg::DownloadUpdated(job) => {
+                    match &job.status {
+                        JobStatus::Completed => {
+                            self.set_status(format!("Downloaded \"{}\"", job.request.title))
+                        }
+                        JobStatus::Skipped => self.set_status(format!(
+                            "Nothing downloaded for \"{}\"",
+                            job.request.title
+                        )),
+                        JobStatus::Failed(_) => {
+                            self.set_status(format!("Download of \"{}\" failed", job.request.title))
+                        }
+                        _ => {}
+                    }
+                    match self.downloads.iter_mut().find(|j| j.id == job.id) {
+                        Some(existing) => *existing = *job,
+                        None => self.downloads.push(*job),
+                    }
+                    if let ActiveView::Downloads = self.active_view {
+                        self.show_downloads(true);
+                    }
+                }
+
                 Msg::Subscribe(_, idx) => {
-                    let mut content_item = self.search_result[idx].clone();
-                    tokio::spawn(async move {
-                        content_item.subscribe().await;
-                    });
+                    if let Some(mut content_item) = self.selected_item(idx) {
+                        tokio::spawn(async move {
+                            content_item.subscribe().await;
+                        });
+                    }
                 }
                 Msg::Unsubscribe(_, idx) => {
-                    let mut content_item = self.search_result[idx].clone();
-                    tokio::spawn(async move {
-                        content_item.unsubscribe().await;
-                    });
+                    if let Some(content_item) = self.selected_item(idx) {
+                        let tx = self.tx.clone();
+                        tokio::spawn(async move {
+                            match content_item.unsubscribe().await {
+                                Ok(unsubscribed) => tx
+                                    .send(Msg::Unsubscribed(Box::new(unsubscribed)))
+                                    .await
+                                    .ok(),
+                                Err(e) => tx.send(Msg::Status(e)).await.ok(),
+                            };
+                        });
+                    }
                 }
-                Msg::Download(_, idx, video_track) => {
-                    let mut content_item = self.search_result[idx].clone();
+                Msg::Unsubscribed(unsubscribed) => {
+                    let grace = Duration::from_secs(config::get().ui.undo_seconds);
+                    self.set_status(undo_notice(&unsubscribed));
+                    self.undo = Some((*unsubscribed, Instant::now() + grace));
+
+                    let tx = self.tx.clone();
                     tokio::spawn(async move {
-                        content_item.download(video_track).await;
+                        tokio::time::sleep(grace).await;
+                        tx.send(Msg::UndoExpired).await.ok();
                     });
+                    match self.active_view {
+                        ActiveView::Feed => self.load_feed(),
+                        ActiveView::Subscriptions => self.load_subscriptions(),
+                        _ => {}
+                    }
                 }
-                Msg::Search(input) => {
-                    let tx = self.tx.clone();
-                    tokio::spawn(async move {
-                        if let Ok(content) = search_content(&input).await {
-                            tx.send(Msg::SearchResults(content)).await.ok();
+                Msg::Undo => match self.undo.take() {
+                    Some((unsubscribed, deadline)) if Instant::now() < deadline => {
+                        let tx = self.tx.clone();
+                        let task = async move {
+                            unsubscribed.restore().await?;
+                            let status = format!(
+                                "Subscribed to {} again",
+                                unsubscribed.channel.channel_username
+                            );
+                            tx.send(Msg::Status(status)).await.ok();
+                            Ok(())
+                        };
+                        match self.active_view {
+                            ActiveView::Feed => self.load_feed_after(task),
+                            ActiveView::Subscriptions => self.load_subscriptions_after(task),
+                            _ => {
+                                let tx = self.tx.clone();
+                                tokio::spawn(async move {
+                                    if let Err(e) = task.await {
+                                        tx.send(Msg::Status(e)).await.ok();
+                                    }
+                                });
+                            }
                         }
-                    });
-                    self.active_view = ActiveView::Idle;
-                    assert!(self.app.active(&Id::Menu).is_ok());
+                    }
+                    _ => self.set_status(String::from("Nothing to undo")),
+                },
+                // An earlier unsubscribe's timer can fire while a later one is still undoable
+                Msg::UndoExpired
+                    if self
+                        .undo
+                        .as_ref()
+                        .is_some_and(|(_, deadline)| Instant::now() >= *deadline) =>
+                {
+                    let notice = self
+                        .undo
+                        .take()
+                        .map(|(unsubscribed, _)| undo_notice(&unsubscribed));
+                    let status = self.app.query(&Id::Label, Attribute::Text).ok().flatten();
+                    if status == notice.map(AttrValue::String) {
+                        self.show_playing();
+                    }
+                }
+                Msg::Download(_, idx, video_track) => match self.selected_item(idx) {
+                    Some(ContentItem::Channel(channel)) => {
+                        let download_type = match video_track {
+                            true => DownloadType::Video,
+                            false => DownloadType::Audio,
+                        };
+                        self.focus_search(SearchTarget::ChannelDownload(channel, download_type));
+                        assert!(self.app.active(&Id::Input).is_ok());
+                    }
+                    Some(content_item) => self.download(content_item, video_track, None),
+                    None => {}
+                },
+                Msg::Clip(_, idx, video_track) => {
+                    if let Some(content_item @ ContentItem::Video(_)) = self.selected_item(idx) {
+                        let download_type = match video_track {
+                            true => DownloadType::Video,
+                            false => DownloadType::Audio,
+                        };
+                        self.focus_search(SearchTarget::Clip(content_item, download_type));
+                        assert!(self.app.active(&Id::Input).is_ok());
+                    }
+                }
+                Msg::ToggleWatched(_, idx) => {
+                    if let (ActiveView::Feed, Some(ContentItem::Video(video))) =
+                        (&self.active_view, self.search_result.get(idx))
+                    {
+                        let (video_id, watched) = (video.id.clone(), !video.watched);
+                        self.load_feed_after(async move { set_watched(&video_id, watched).await });
+                    }
+                }
+                Msg::MarkChannelWatched(_, idx) => {
+                    if let (ActiveView::Feed, Some(ContentItem::Video(video))) =
+                        (&self.active_view, self.search_result.get(idx))
+                    {
+                        let channel_id = video.channel.id.clone();
+                        self.load_feed_after(async move {
+                            mark_all_watched(Some(&channel_id), None).await
+                        });
+                    }
+                }
+                Msg::MarkAllWatched => {
+                    if let ActiveView::Feed = self.active_view {
+                        self.load_feed_after(mark_all_watched(None, self.feed_filter.group));
+                    }
+                }
+                Msg::ToggleHideWatched => {
+                    if let ActiveView::Feed = self.active_view {
+                        self.feed_filter.hide_watched = !self.feed_filter.hide_watched;
+                        self.load_feed();
+                    }
+                }
+                Msg::ToggleUnwatchedFirst => {
+                    if let ActiveView::Feed = self.active_view {
+                        self.feed_filter.unwatched_first = !self.feed_filter.unwatched_first;
+                        self.load_feed();
+                    }
+                }
+                Msg::NextGroup => {
+                    if let ActiveView::Feed = self.active_view {
+                        let tx = self.tx.clone();
+                        let current = self.feed_filter.group;
+                        tokio::spawn(async move {
+                            match groups::next_group(current).await {
+                                Ok(group) => tx.send(Msg::FeedGroup(group)).await.ok(),
+                                Err(e) => tx.send(Msg::Status(e)).await.ok(),
+                            };
+                        });
+                    }
+                }
+                Msg::EditGroups(_, idx) => {
+                    if let (ActiveView::Subscriptions, Some(subscription)) =
+                        (&self.active_view, self.subscriptions.get(idx))
+                    {
+                        let channel =
+                            Channel::new(&subscription.channel_id, &subscription.channel_username);
+                        let current = subscription.groups.clone().unwrap_or_default();
+                        self.focus_search(SearchTarget::Groups(channel, current));
+                        assert!(self.app.active(&Id::Input).is_ok());
+                    }
+                }
+                Msg::RenameSubscription(_, idx) => {
+                    if let (ActiveView::Subscriptions, Some(ContentItem::Channel(channel))) =
+                        (&self.active_view, self.selected_item(idx))
+                    {
+                        self.focus_search(SearchTarget::Rename(channel));
+                        assert!(self.app.active(&Id::Input).is_ok());
+                    }
+                }
+                Msg::RefreshSubscription(_, idx) => {
+                    if let (ActiveView::Subscriptions, Some(ContentItem::Channel(channel))) =
+                        (&self.active_view, self.selected_item(idx))
+                    {
+                        self.set_status(format!("Refreshing {}...", channel.username));
+                        let tx = self.tx.clone();
+                        self.load_subscriptions_after(async move {
+                            refresh_subscription(&channel.id).await?;
+                            let status = format!("Refreshed {}", channel.username);
+                            tx.send(Msg::Status(status)).await.ok();
+                            Ok(())
+                        });
+                    }
+                }
+                Msg::CycleSort => {
+                    if let ActiveView::Subscriptions = self.active_view {
+                        self.subscription_sort = self.subscription_sort.next();
+                        self.load_subscriptions();
+                    }
+                }
+                Msg::FeedGroup(group) => {
+                    self.feed_filter.group = group.as_ref().map(|group| group.id);
+                    self.feed_group = group;
+                    self.load_feed();
                 }
+                Msg::PauseDownload(_, idx) => {
+                    self.control_download(idx, |id| downloads::get().toggle_pause(id))
+                }
+                Msg::CancelDownload(_, idx) => {
+                    self.control_download(idx, |id| downloads::get().cancel(id))
+                }
+                Msg::RetryDownload(_, idx) => {
+                    self.control_download(idx, |id| downloads::get().retry(id))
+                }
+                Msg::Delete(_, idx) if matches!(self.active_view, ActiveView::Downloads) => {
+                    self.control_download(idx, |id| downloads::get().dismiss(id));
+                    self.downloads = downloads::get().jobs();
+                    self.show_downloads(true);
+                }
+                Msg::Delete(_, idx) if matches!(self.active_view, ActiveView::Library) => {
+                    if let Some(entry) = self.library.get(idx) {
+                        let tx = self.tx.clone();
+                        let path = entry.path.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = library::delete(&path).await {
+                                tx.send(Msg::Status(e)).await.ok();
+                            }
+                            if let Ok(entries) = library::get_library("").await {
+                                tx.send(Msg::LibraryResults(entries)).await.ok();
+                            }
+                        });
+                    }
+                }
+                Msg::Delete(_, idx) => {
+                    if let (ActiveView::History, Some(entry)) =
+                        (&self.active_view, self.history.get(idx))
+                    {
+                        let tx = self.tx.clone();
+                        let video_id = entry.video_id.clone();
+                        let query = self.history_query.clone();
+                        tokio::spawn(async move {
+                            let _ = history::delete(&video_id).await;
+                            if let Ok(entries) = history::get_history(&query).await {
+                                tx.send(Msg::HistoryResults(entries)).await.ok();
+                            }
+                        });
+                    }
+                }
+                Msg::ClearAll => {
+                    if let ActiveView::History = self.active_view {
+                        self.focus_search(SearchTarget::ClearHistory);
+                        assert!(self.app.active(&Id::Input).is_ok());
+                    }
+                }
+                Msg::FocusSearch => match self.active_view {
+                    ActiveView::History => {
+                        self.focus_search(SearchTarget::History);
+                        assert!(self.app.active(&Id::Input).is_ok());
+                    }
+                    ActiveView::Library => {
+                        self.focus_search(SearchTarget::Library);
+                        assert!(self.app.active(&Id::Input).is_ok());
+                    }
+                    _ => {}
+                },
+                Msg::ToggleLocalSearch => match self.search_target {
+                    SearchTarget::Youtube => self.focus_search(SearchTarget::Local),
+                    SearchTarget::Local => self.focus_search(SearchTarget::Youtube),
+                    _ => {}
+                },
+                Msg::Search(input) => match &self.search_target {
+                    SearchTarget::Youtube => {
+                        let tx = self.tx.clone();
+                        tokio::spawn(async move {
+                            if let Ok(content) = search_content(&input).await {
+                                tx.send(Msg::SearchResults(content)).await.ok();
+                            }
+                        });
+                        self.active_view = ActiveView::Idle;
+                        assert!(self.app.active(&Id::Menu).is_ok());
+                    }
+                    SearchTarget::Local => {
+                        let tx = self.tx.clone();
+                        tokio::spawn(async move {
+                            match local_search::search(&input).await {
+                                Ok(matches) => tx.send(Msg::LocalResults(matches)).await.ok(),
+                                Err(e) => tx.send(Msg::Status(e)).await.ok(),
+                            };
+                        });
+                        self.active_view = ActiveView::Idle;
+                        assert!(self.app.active(&Id::Menu).is_ok());
+                    }
+                    SearchTarget::History => {
+                        self.load_history(input);
+                        assert!(self.app.active(&Id::Menu).is_ok());
+                    }
+                    SearchTarget::Library => {
+                        self.load_library(input);
+                        assert!(self.app.active(&Id::Menu).is_ok());
+                    }
+                    SearchTarget::ChannelDownload(channel, download_type) => {
+                        let status = match DownloadScope::parse_channel_input(&input) {
+                            Ok(scope) => {
+                                channel.download(*download_type, scope);
+                                format!("Queued download from {}", channel.username)
+                            }
+                            Err(e) => e,
+                        };
+                        self.set_status(status);
+                        self.focus_search(SearchTarget::Youtube);
+                        assert!(self.app.active(&Id::Menu).is_ok());
+                    }
+                    SearchTarget::Rename(channel) => {
+                        let channel_id = channel.id.clone();
+                        self.load_subscriptions_after(async move {
+                            subscriptions::rename(&channel_id, &input).await
+                        });
+                        self.focus_search(SearchTarget::Youtube);
+                        assert!(self.app.active(&Id::Menu).is_ok());
+                    }
+                    SearchTarget::Groups(channel, _) => {
+                        let channel_id = channel.id.clone();
+                        self.load_subscriptions_after(async move {
+                            let names = input.split(',').collect::<Vec<_>>();
+                            groups::set_groups(&channel_id, &names).await
+                        });
+                        self.focus_search(SearchTarget::Youtube);
+                        assert!(self.app.active(&Id::Menu).is_ok());
+                    }
+                    SearchTarget::ClearHistory => {
+                        match input.trim().eq_ignore_ascii_case("yes") {
+                            true => {
+                                let tx = self.tx.clone();
+                                tokio::spawn(async move {
+                                    match history::clear().await {
+                                        Ok(()) => {
+                                            tx.send(Msg::HistoryResults(Vec::new())).await.ok()
+                                        }
+                                        Err(e) => tx.send(Msg::Status(e)).await.ok(),
+                                    };
+                                });
+                            }
+                            false => self.set_status(String::from("History kept")),
+                        }
+                        self.focus_search(SearchTarget::Youtube);
+                        assert!(self.app.active(&Id::Menu).is_ok());
+                    }
+                    SearchTarget::Clip(content_item, download_type) => {
+                        let video_track = *download_type == DownloadType::Video;
+                        let queued = DownloadScope::parse_section_input(&input)
+                            .and_then(|section| content_item.clip(video_track, section));
+                        let status = match queued {
+                            Ok(()) => format!("Queued clip of \"{}\"", content_item.title()),
+                            Err(e) => e,
+                        };
+                        self.set_status(status);
+                        self.focus_search(SearchTarget::Youtube);
+                        assert!(self.app.active(&Id::Menu).is_ok());
+                    }
+                },
                 Msg::SearchResults(content) => {
                     self.search_result = content.clone();
                     let menu_items = content
@@ -217,9 +1070,143 @@ where
                             .is_ok()
                     );
                 }
+                Msg::LocalResults(matches) => {
+                    if matches.is_empty() {
+                        self.set_status(String::from("Nothing found offline"));
+                    }
+                    let menu_items = matches.iter().map(LocalMatch::describe).collect();
+                    self.local_results = matches;
+                    self.active_view = ActiveView::LocalSearch;
+                    assert!(
+                        self.app
+                            .remount(
+                                Id::Menu,
+                                Box::new(Menu::new(menu_items).title("Offline results")),
+                                Vec::default()
+                            )
+                            .is_ok()
+                    );
+                }
+                Msg::FeedResults(content) => {
+                    let menu_items = content
+                        .iter()
+                        .map(|content_item| match content_item {
+                            ContentItem::Video(video) if video.watched => {
+                                format!("✓ {}", video.title)
+                            }
+                            ContentItem::Video(video) => format!("  {}", video.title),
+                            ContentItem::Channel(channel) => channel.username.clone(),
+                            ContentItem::Playlist(playlist) => playlist.title.clone(),
+                        })
+                        .collect();
+                    let group = self.feed_group.as_ref().map_or("All", |group| &group.name);
+                    let mut title = format!("Feed: {}", group);
+                    if self.feed_filter.hide_watched {
+                        title.push_str(" (unwatched only)");
+                    } else if self.feed_filter.unwatched_first {
+                        title.push_str(" (unwatched first)");
+                    }
+                    self.search_result = content;
+                    self.active_view = ActiveView::Feed;
+                    assert!(
+                        self.app
+                            .remount(
+                                Id::Menu,
+                                Box::new(Menu::new(menu_items).title(title)),
+                                Vec::default()
+                            )
+                            .is_ok()
+                    );
+                }
+                Msg::SubscriptionResults(subscriptions) => {
+                    let selected = match self.active_view {
+                        ActiveView::Subscriptions => self.menu_selection(),
+                        _ => 0,
+                    };
+                    let menu_items = subscriptions.iter().map(Subscription::describe).collect();
+                    let title = format!("Subscriptions ({})", self.subscription_sort.label());
+                    self.subscriptions = subscriptions;
+                    self.active_view = ActiveView::Subscriptions;
+                    assert!(
+                        self.app
+                            .remount(
+                                Id::Menu,
+                                Box::new(Menu::new(menu_items).title(title).selected(selected)),
+                                Vec::default()
+                            )
+                            .is_ok()
+                    );
+                    assert!(self.app.active(&Id::Menu).is_ok());
+                }
+                Msg::HistoryResults(entries) => {
+                    let menu_items = entries
+                        .iter()
+                        .map(|entry| {
+                            let progress = match (entry.completed, entry.duration) {
+                                (true, _) => String::from("watched"),
+                                (false, Some(duration)) => format!(
+                                    "{} / {}",
+                                    format_position(entry.last_position),
+                                    format_position(duration)
+                                ),
+                                (false, None) => format_position(entry.last_position),
+                            };
+                            format!(
+                                "{} - {} [{}]",
+                                entry.title, entry.channel_username, progress
+                            )
+                        })
+                        .collect();
+                    self.history = entries;
+                    self.active_view = ActiveView::History;
+                    assert!(
+                        self.app
+                            .remount(Id::Menu, Box::new(Menu::new(menu_items)), Vec::default())
+                            .is_ok()
+                    );
+                    assert!(self.app.active(&Id::Menu).is_ok());
+                }
+                Msg::LibraryResults(entries) => {
+                    let menu_items = entries
+                        .iter()
+                        .map(|entry| {
+                            let extension = Path::new(&entry.path)
+                                .extension()
+                                .map(|ext| ext.to_string_lossy().to_string())
+                                .unwrap_or_default();
+                            format!(
+                                "{} - {} [{} {}, {}]",
+                                entry.title,
+                                entry.channel_username,
+                                extension,
+                                entry.format,
+                                format_bytes(entry.size as f64)
+                            )
+                        })
+                        .collect();
+                    self.library = entries;
+                    self.active_view = ActiveView::Library;
+                    assert!(
+                        self.app
+                            .remount(
+                                Id::Menu,
+                                Box::new(Menu::new(menu_items).title("Library")),
+                                Vec::default()
+                            )
+                            .is_ok()
+                    );
+                    assert!(self.app.active(&Id::Menu).is_ok());
+                }
                 Msg::Exit => match self.active_view {
                     ActiveView::MainMenu => return Some(Msg::AppClose),
-                    ActiveView::SearchResult | ActiveView::Idle => self.go_to_main_menu(),
+                    ActiveView::SearchResult
+                    | ActiveView::LocalSearch
+                    | ActiveView::Feed
+                    | ActiveView::Downloads
+                    | ActiveView::History
+                    | ActiveView::Library
+                    | ActiveView::Subscriptions
+                    | ActiveView::Idle => self.go_to_main_menu(),
                 },
                 _ => {}
             }
@@ -227,3 +1214,11 @@ where
         None
     }
 }
+
+fn undo_notice(unsubscribed: &Unsubscribed) -> String {
+    format!(
+        "Unsubscribed from {}, press {} to undo",
+        unsubscribed.channel.channel_username,
+        config::get().keys.undo
+    )
+}
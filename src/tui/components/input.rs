@@ -129,7 +129,14 @@ impl MockComponent for Input {
     }
 
     fn attr(&mut self, attr: Attribute, value: AttrValue) {
-        self.props.set(attr, value);
+        match (attr, value) {
+            // Replaces the text being edited
+            (Attribute::Value, AttrValue::String(text)) => {
+                self.states.input = text;
+                self.states.cursor_at_end();
+            }
+            (attr, value) => self.props.set(attr, value),
+        }
     }
 
     fn state(&self) -> State {
@@ -240,6 +247,9 @@ impl Component<Msg, NoUserEvent> for Input {
                 code: Key::Enter, ..
             }) => Cmd::Submit,
             Event::Keyboard(KeyEvent { code: Key::Esc, .. }) => Cmd::Cancel,
+            Event::Keyboard(KeyEvent { code: Key::Tab, .. }) => {
+                return Some(Msg::ToggleLocalSearch);
+            }
             _ => Cmd::None,
         };
 
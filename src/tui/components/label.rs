@@ -111,8 +111,8 @@ impl Component<Msg, NoUserEvent> for Label {
             Event::Keyboard(KeyEvent {
                 code: Key::Esc,
                 modifiers: KeyModifiers::NONE,
-            }) => return Some(Msg::AppClose),
-            _ => return Some(Msg::None),
+            }) => Some(Msg::AppClose),
+            _ => Some(Msg::None),
         }
     }
 }
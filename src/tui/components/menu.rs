@@ -9,6 +9,8 @@ use tuirealm::{
 };
 
 use super::super::tui::Msg;
+use crate::config;
+use crate::local_search::{MATCH_END, MATCH_START};
 
 pub struct Menu {
     component: List,
@@ -16,20 +18,24 @@ pub struct Menu {
 }
 
 impl Menu {
+    /// Menu listing `items`. Parts of an item between `MATCH_START` and `MATCH_END` are shown
+    /// highlighted.
     pub fn new(items: Vec<String>) -> Self {
         let mut table = TableBuilder::default();
-        if let Some((last, head)) = items.split_last() {
-            for item in head {
-                table.add_col(TextSpan::from(item.clone())).add_row();
+        for (index, item) in items.iter().enumerate() {
+            if index > 0 {
+                table.add_row();
+            }
+            for span in spans(item) {
+                table.add_col(span);
             }
-            table.add_col(TextSpan::from(last.clone()));
         }
 
         Self {
             component: List::default()
                 .foreground(Color::Reset)
                 .background(Color::Reset)
-                .highlighted_color(Color::Yellow)
+                .highlighted_color(config::get().ui.highlight)
                 .highlighted_str(">> ")
                 .modifiers(TextModifiers::BOLD)
                 .scroll(true)
@@ -43,6 +49,49 @@ impl Menu {
     }
 }
 
+impl Menu {
+    pub fn title<S>(mut self, title: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        self.component = self
+            .component
+            .title(title.as_ref().to_string(), Alignment::Center);
+        self
+    }
+
+    /// Starts with the item at `index` selected, e.g. to keep the selection across a refresh.
+    pub fn selected(mut self, index: usize) -> Self {
+        self.component = self.component.selected_line(index);
+        self
+    }
+}
+
+/// Splits `item` at its match markers, highlighting the matched parts.
+fn spans(item: &str) -> Vec<TextSpan> {
+    let mut spans = Vec::new();
+    let mut text = String::new();
+
+    for c in item.chars() {
+        match c {
+            MATCH_START => spans.push(TextSpan::from(std::mem::take(&mut text))),
+            MATCH_END => spans.push(
+                TextSpan::from(std::mem::take(&mut text))
+                    .fg(config::get().ui.matched)
+                    .underlined(),
+            ),
+            c => text.push(c),
+        }
+    }
+    spans.push(TextSpan::from(text));
+
+    spans.retain(|span| !span.content.is_empty());
+    if spans.is_empty() {
+        spans.push(TextSpan::from(""));
+    }
+    spans
+}
+
 impl MockComponent for Menu {
     fn view(&mut self, frame: &mut Frame, area: Rect) {
         self.component.view(frame, area);
@@ -73,18 +122,18 @@ impl Component<Msg, NoUserEvent> for Menu {
             None
         };
 
+        let keys = &config::get().keys;
+
         match ev {
-            Event::Keyboard(KeyEvent {
-                code: Key::Down | Key::Char('j'),
-                ..
-            }) => {
+            Event::Keyboard(KeyEvent { code, .. })
+                if code == Key::Down || code == Key::Char(keys.down) =>
+            {
                 self.perform(Cmd::Move(Direction::Down));
                 Some(Msg::None)
             }
-            Event::Keyboard(KeyEvent {
-                code: Key::Up | Key::Char('k'),
-                ..
-            }) => {
+            Event::Keyboard(KeyEvent { code, .. })
+                if code == Key::Up || code == Key::Char(keys.up) =>
+            {
                 self.perform(Cmd::Move(Direction::Up));
                 Some(Msg::None)
             }
@@ -98,9 +147,26 @@ impl Component<Msg, NoUserEvent> for Menu {
                 }
             }
             Event::Keyboard(KeyEvent {
-                code: Key::Char('s'),
-                ..
-            }) => {
+                code: Key::Char(c), ..
+            }) if c == keys.play_audio => {
+                if let Some((item, index)) = current_selection {
+                    Some(Msg::PlayAudio(item, index))
+                } else {
+                    Some(Msg::None)
+                }
+            }
+            Event::Keyboard(KeyEvent {
+                code: Key::Char(c), ..
+            }) if c == keys.pick_format => {
+                if let Some((item, index)) = current_selection {
+                    Some(Msg::PickFormat(item, index))
+                } else {
+                    Some(Msg::None)
+                }
+            }
+            Event::Keyboard(KeyEvent {
+                code: Key::Char(c), ..
+            }) if c == keys.subscribe => {
                 if let Some((item, index)) = current_selection {
                     Some(Msg::Subscribe(item, index))
                 } else {
@@ -108,9 +174,8 @@ impl Component<Msg, NoUserEvent> for Menu {
                 }
             }
             Event::Keyboard(KeyEvent {
-                code: Key::Char('u'),
-                ..
-            }) => {
+                code: Key::Char(c), ..
+            }) if c == keys.unsubscribe => {
                 if let Some((item, index)) = current_selection {
                     Some(Msg::Unsubscribe(item, index))
                 } else {
@@ -118,9 +183,8 @@ impl Component<Msg, NoUserEvent> for Menu {
                 }
             }
             Event::Keyboard(KeyEvent {
-                code: Key::Char('d'),
-                ..
-            }) => {
+                code: Key::Char(c), ..
+            }) if c == keys.download_video => {
                 if let Some((item, index)) = current_selection {
                     Some(Msg::Download(item, index, true))
                 } else {
@@ -128,15 +192,137 @@ impl Component<Msg, NoUserEvent> for Menu {
                 }
             }
             Event::Keyboard(KeyEvent {
-                code: Key::Char('m'),
-                ..
-            }) => {
+                code: Key::Char(c), ..
+            }) if c == keys.download_audio => {
                 if let Some((item, index)) = current_selection {
                     Some(Msg::Download(item, index, false))
                 } else {
                     Some(Msg::None)
                 }
             }
+            Event::Keyboard(KeyEvent {
+                code: Key::Char(c), ..
+            }) if c == keys.pause_download => {
+                if let Some((item, index)) = current_selection {
+                    Some(Msg::PauseDownload(item, index))
+                } else {
+                    Some(Msg::None)
+                }
+            }
+            Event::Keyboard(KeyEvent {
+                code: Key::Char(c), ..
+            }) if c == keys.cancel_download => {
+                if let Some((item, index)) = current_selection {
+                    Some(Msg::CancelDownload(item, index))
+                } else {
+                    Some(Msg::None)
+                }
+            }
+            Event::Keyboard(KeyEvent {
+                code: Key::Char(c), ..
+            }) if c == keys.retry_download => {
+                if let Some((item, index)) = current_selection {
+                    Some(Msg::RetryDownload(item, index))
+                } else {
+                    Some(Msg::None)
+                }
+            }
+            Event::Keyboard(KeyEvent {
+                code: Key::Char(c), ..
+            }) if c == keys.clip => {
+                if let Some((item, index)) = current_selection {
+                    Some(Msg::Clip(item, index, true))
+                } else {
+                    Some(Msg::None)
+                }
+            }
+            Event::Keyboard(KeyEvent {
+                code: Key::Char(c), ..
+            }) if c == keys.audio_clip => {
+                if let Some((item, index)) = current_selection {
+                    Some(Msg::Clip(item, index, false))
+                } else {
+                    Some(Msg::None)
+                }
+            }
+            Event::Keyboard(KeyEvent {
+                code: Key::Char(c), ..
+            }) if c == keys.toggle_watched => {
+                if let Some((item, index)) = current_selection {
+                    Some(Msg::ToggleWatched(item, index))
+                } else {
+                    Some(Msg::None)
+                }
+            }
+            Event::Keyboard(KeyEvent {
+                code: Key::Char(c), ..
+            }) if c == keys.mark_channel_watched => {
+                if let Some((item, index)) = current_selection {
+                    Some(Msg::MarkChannelWatched(item, index))
+                } else {
+                    Some(Msg::None)
+                }
+            }
+            Event::Keyboard(KeyEvent {
+                code: Key::Char(c), ..
+            }) if c == keys.mark_all_watched => Some(Msg::MarkAllWatched),
+            Event::Keyboard(KeyEvent {
+                code: Key::Char(c), ..
+            }) if c == keys.hide_watched => Some(Msg::ToggleHideWatched),
+            Event::Keyboard(KeyEvent {
+                code: Key::Char(c), ..
+            }) if c == keys.unwatched_first => Some(Msg::ToggleUnwatchedFirst),
+            Event::Keyboard(KeyEvent {
+                code: Key::Char(c), ..
+            }) if c == keys.next_group => Some(Msg::NextGroup),
+            Event::Keyboard(KeyEvent {
+                code: Key::Char(c), ..
+            }) if c == keys.edit_groups => {
+                if let Some((item, index)) = current_selection {
+                    Some(Msg::EditGroups(item, index))
+                } else {
+                    Some(Msg::None)
+                }
+            }
+            Event::Keyboard(KeyEvent {
+                code: Key::Char(c), ..
+            }) if c == keys.rename => {
+                if let Some((item, index)) = current_selection {
+                    Some(Msg::RenameSubscription(item, index))
+                } else {
+                    Some(Msg::None)
+                }
+            }
+            Event::Keyboard(KeyEvent {
+                code: Key::Char(c), ..
+            }) if c == keys.refresh => {
+                if let Some((item, index)) = current_selection {
+                    Some(Msg::RefreshSubscription(item, index))
+                } else {
+                    Some(Msg::None)
+                }
+            }
+            Event::Keyboard(KeyEvent {
+                code: Key::Char(c), ..
+            }) if c == keys.sort => Some(Msg::CycleSort),
+            Event::Keyboard(KeyEvent {
+                code: Key::Char(c), ..
+            }) if c == keys.delete => {
+                if let Some((item, index)) = current_selection {
+                    Some(Msg::Delete(item, index))
+                } else {
+                    Some(Msg::None)
+                }
+            }
+            Event::Keyboard(KeyEvent {
+                code: Key::Char(c), ..
+            }) if c == keys.clear_all => Some(Msg::ClearAll),
+            Event::Keyboard(KeyEvent {
+                code: Key::Char(c), ..
+            }) if c == keys.search => Some(Msg::FocusSearch),
+            Event::Keyboard(KeyEvent {
+                code: Key::Char(c), ..
+            }) if c == keys.undo => Some(Msg::Undo),
             Event::Keyboard(KeyEvent { code: Key::Esc, .. }) => Some(Msg::Exit),
             _ => Some(Msg::None),
         }
@@ -1,3 +1,4 @@
 pub mod app;
 pub mod components;
+#[allow(clippy::module_inception)]
 pub mod tui;
@@ -1,7 +1,12 @@
 //! ## Tui
 
+use crate::downloads::{self, Job};
+use crate::groups::Group;
+use crate::local_search::LocalMatch;
+use crate::subscriptions::Subscription;
 use crate::tui::app::model::Model;
-use crate::types::ContentItem;
+use crate::types::{ContentItem, HistoryDB, LibraryDB};
+use crate::youtube::{Unsubscribed, formats::Format};
 use tuirealm::application::PollStrategy;
 use tuirealm::{AttrValue, Attribute, Update};
 
@@ -11,11 +16,44 @@ pub enum Msg {
     Exit,
     Clock,
     MenuSelected(String, usize),
+    PlayAudio(String, usize),
+    PlaybackEnded(String, Result<(), String>),
+    DownloadUpdated(Box<Job>),
+    PauseDownload(String, usize),
+    CancelDownload(String, usize),
+    RetryDownload(String, usize),
+    Status(String),
+    PickFormat(String, usize),
+    FormatResults(Result<Vec<Format>, String>),
     Subscribe(String, usize),
     Unsubscribe(String, usize),
+    Unsubscribed(Box<Unsubscribed>),
+    Undo,
+    UndoExpired,
     Download(String, usize, bool),
+    Clip(String, usize, bool),
+    Delete(String, usize),
+    ClearAll,
+    FocusSearch,
+    ToggleLocalSearch,
     Search(String),
     SearchResults(Vec<ContentItem>),
+    LocalResults(Vec<LocalMatch>),
+    FeedResults(Vec<ContentItem>),
+    ToggleWatched(String, usize),
+    MarkChannelWatched(String, usize),
+    MarkAllWatched,
+    ToggleHideWatched,
+    ToggleUnwatchedFirst,
+    NextGroup,
+    FeedGroup(Option<Group>),
+    EditGroups(String, usize),
+    RenameSubscription(String, usize),
+    RefreshSubscription(String, usize),
+    CycleSort,
+    SubscriptionResults(Vec<Subscription>),
+    HistoryResults(Vec<HistoryDB>),
+    LibraryResults(Vec<LibraryDB>),
     None,
 }
 
@@ -23,6 +61,7 @@ pub enum Msg {
 pub enum Id {
     Clock,
     Menu,
+    FormatPicker,
     SearchMenu,
     Input,
     Label,
@@ -32,7 +71,23 @@ pub enum Id {
 pub fn main() {
     let mut model = Model::default();
     let (tx, mut rx) = tokio::sync::mpsc::channel(1024);
-    model.tx = tx;
+    model.tx = tx.clone();
+
+    // Forward download manager events to the update loop
+    let mut download_events = downloads::get().subscribe();
+    tokio::spawn(async move {
+        loop {
+            match download_events.recv().await {
+                Ok(job) => {
+                    if tx.send(Msg::DownloadUpdated(Box::new(job))).await.is_err() {
+                        break;
+                    }
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
 
     let _ = model.terminal.enter_alternate_screen();
     let _ = model.terminal.enable_raw_mode();
@@ -1,18 +1,23 @@
 use core::fmt;
 
 use chrono::{DateTime, Utc};
-use ratatui::style::{Color, Modifier, Style};
+use ratatui::style::{Modifier, Style};
 use ratatui::text::{Line, Span};
 
-use crate::config::play_video_command;
-use crate::youtube::download::{DownloadType, download_from_yt};
-use crate::youtube::subscribe_to_channel;
+use crate::config::{self, PlaybackMode};
+use crate::downloads::{self, DownloadRequest};
+use crate::local_search::{self, LocalMatch};
+use crate::player;
+use crate::youtube::download::{DownloadScope, DownloadType};
+use crate::youtube::{Unsubscribed, subscribe_to_channel, unsubscribe};
 
+#[derive(Clone, PartialEq)]
 pub struct ChannelDB {
     pub channel_id: String,
     pub channel_username: String,
 }
 
+#[derive(Clone, PartialEq)]
 pub struct VideoDB {
     pub id: String,
     pub title: String,
@@ -20,6 +25,78 @@ pub struct VideoDB {
     pub published_at: String,
     pub channel_id: String,
     pub channel_username: String,
+    pub watched: bool,
+    pub description: String,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct HistoryDB {
+    pub video_id: String,
+    pub title: String,
+    pub url: String,
+    pub channel_id: String,
+    pub channel_username: String,
+    pub started_at: String,
+    pub last_position: f64,
+    pub duration: Option<f64>,
+    pub completed: bool,
+}
+
+impl From<HistoryDB> for Video {
+    fn from(entry: HistoryDB) -> Self {
+        Video {
+            id: entry.video_id,
+            title: entry.title,
+            url: entry.url,
+            tag: String::new(),
+            channel: Channel::new(&entry.channel_id, &entry.channel_username),
+            published_at: entry.started_at.parse().unwrap_or_else(|_| Utc::now()),
+            watched: entry.completed,
+            description: String::new(),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct LibraryDB {
+    pub path: String,
+    pub video_id: String,
+    pub title: String,
+    pub channel_username: String,
+    pub download_type: String,
+    pub format: String,
+    pub size: i64,
+    pub downloaded_at: String,
+}
+
+impl From<LibraryDB> for Video {
+    fn from(entry: LibraryDB) -> Self {
+        Video {
+            url: format!("https://www.youtube.com/watch?v={}", entry.video_id),
+            id: entry.video_id,
+            title: entry.title,
+            tag: String::new(),
+            channel: Channel::new("", &entry.channel_username),
+            published_at: entry.downloaded_at.parse().unwrap_or_else(|_| Utc::now()),
+            watched: false,
+            description: String::new(),
+        }
+    }
+}
+
+impl From<LocalMatch> for Video {
+    fn from(entry: LocalMatch) -> Self {
+        Video {
+            id: entry.video_id,
+            title: local_search::unmarked(&entry.title),
+            url: entry.url,
+            tag: String::new(),
+            channel: Channel::new(&entry.channel_id, &local_search::unmarked(&entry.channel)),
+            published_at: Utc::now(),
+            watched: false,
+            description: String::new(),
+        }
+    }
 }
 
 #[derive(Clone, PartialEq)]
@@ -30,7 +107,7 @@ pub enum ContentItem {
 }
 
 impl ContentItem {
-    pub fn display(&self, selected: bool) -> Vec<Line> {
+    pub fn display(&self, selected: bool) -> Vec<Line<'_>> {
         match self {
             ContentItem::Video(video_props) => video_props.display(selected),
             ContentItem::Channel(channel_props) => channel_props.display(selected),
@@ -38,10 +115,12 @@ impl ContentItem {
         }
     }
 
-    pub async fn unsubscribe(&mut self) {
+    /// Unsubscribes from the channel of the item, returning what is needed to undo it.
+    pub async fn unsubscribe(&self) -> Result<Unsubscribed, String> {
         match self {
-            ContentItem::Video(v) => v.unsubscribe().await,
-            _ => {}
+            ContentItem::Video(v) => unsubscribe(&v.channel.id).await,
+            ContentItem::Channel(c) => unsubscribe(&c.id).await,
+            ContentItem::Playlist(_) => Err(String::from("Playlists have no subscription")),
         }
     }
 
@@ -53,23 +132,67 @@ impl ContentItem {
         }
     }
 
-    pub async fn download(&mut self, video_track: bool) {
+    /// Queues a download of this item in the download manager.
+    pub fn download(&self, video_track: bool, format: Option<String>) -> Result<(), String> {
         let download_type = match video_track {
             true => DownloadType::Video,
             false => DownloadType::Audio,
         };
 
         match self {
-            ContentItem::Video(v) => v.download(download_type).await,
-            _ => {}
+            ContentItem::Video(v) => v.download(download_type, format),
+            ContentItem::Playlist(p) => p.download(download_type, format),
+            ContentItem::Channel(_) => Err(String::from(
+                "Pick the latest uploads or a date range to download from a channel",
+            )),
         }
     }
 
-    pub async fn play(&mut self) {
-        match self {
-            ContentItem::Video(v) => v.play().await,
-            _ => {}
+    /// Queues a download of the part of this video picked by `section`.
+    pub fn clip(&self, video_track: bool, section: DownloadScope) -> Result<(), String> {
+        let ContentItem::Video(video) = self else {
+            return Err(String::from("Only videos can be clipped"));
         };
+
+        downloads::get().enqueue(DownloadRequest {
+            url: video.url.clone(),
+            title: format!(
+                "{} ({})",
+                video.title,
+                section.section_label().unwrap_or_default()
+            ),
+            channel: Some(video.channel.username.clone()),
+            download_type: match video_track {
+                true => DownloadType::Video,
+                false => DownloadType::Audio,
+            },
+            scope: section,
+            format: None,
+            match_filter: None,
+            rule_channel_id: None,
+        });
+        Ok(())
+    }
+
+    pub fn title(&self) -> &str {
+        match self {
+            ContentItem::Video(video) => &video.title,
+            ContentItem::Channel(channel) => &channel.username,
+            ContentItem::Playlist(playlist) => &playlist.title,
+        }
+    }
+
+    /// Plays the item; `notify` receives messages worth showing while it plays.
+    pub async fn play(
+        &mut self,
+        mode: PlaybackMode,
+        format: Option<String>,
+        notify: impl Fn(String),
+    ) -> Result<(), String> {
+        match self {
+            ContentItem::Video(v) => v.play(mode, format, notify).await,
+            _ => Err(String::from("Only videos can be played")),
+        }
     }
 }
 
@@ -81,20 +204,23 @@ pub struct Video {
     pub tag: String,
     pub channel: Channel,
     pub published_at: DateTime<Utc>,
+    pub watched: bool,
+    /// Start of the description, as shown in listings.
+    pub description: String,
 }
 
 impl fmt::Display for Video {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Id: {}\n", self.id)?;
-        write!(f, "Title: {}\n", self.title)?;
-        write!(f, "Url: {}\n", self.url)?;
-        write!(f, "Channel:\n")?;
-        write!(f, "  Id: {}\n", self.channel.id)?;
-        write!(f, "  Username: {}\n", self.channel.username)?;
-        write!(f, "  Url: {}\n", self.channel.url)?;
-        write!(f, "Published at: {}\n", self.published_at)?;
+        writeln!(f, "Id: {}", self.id)?;
+        writeln!(f, "Title: {}", self.title)?;
+        writeln!(f, "Url: {}", self.url)?;
+        writeln!(f, "Channel:")?;
+        writeln!(f, "  Id: {}", self.channel.id)?;
+        writeln!(f, "  Username: {}", self.channel.username)?;
+        writeln!(f, "  Url: {}", self.channel.url)?;
+        writeln!(f, "Published at: {}", self.published_at)?;
         if !self.tag.is_empty() {
-            write!(f, "Tag: {}\n", self.tag)?;
+            writeln!(f, "Tag: {}", self.tag)?;
         }
 
         Ok(())
@@ -102,22 +228,25 @@ impl fmt::Display for Video {
 }
 
 impl Video {
-    fn display(&self, selected: bool) -> Vec<Line> {
+    fn display(&self, selected: bool) -> Vec<Line<'_>> {
         if selected {
             return vec![
                 Line::from(vec![
                     Span::styled(
                         format!("> {}\n", self.title),
                         Style::default()
-                            .fg(Color::Yellow)
+                            .fg(config::get().ui.highlight)
                             .add_modifier(Modifier::BOLD),
                     ),
-                    Span::styled(format!(" {}\n", self.tag), Style::default().fg(Color::Blue)),
+                    Span::styled(
+                        format!(" {}\n", self.tag),
+                        Style::default().fg(config::get().ui.tag),
+                    ),
                 ]),
                 Line::from(vec![Span::styled(
                     format!("  {}", self.channel.username),
                     Style::default()
-                        .fg(Color::Yellow)
+                        .fg(config::get().ui.highlight)
                         .add_modifier(Modifier::BOLD),
                 )]),
             ];
@@ -126,7 +255,10 @@ impl Video {
         vec![
             Line::from(vec![
                 Span::raw(format!("  {}\n", self.title)),
-                Span::styled(format!(" {}\n", self.tag), Style::default().fg(Color::Blue)),
+                Span::styled(
+                    format!(" {}\n", self.tag),
+                    Style::default().fg(config::get().ui.tag),
+                ),
             ]),
             Line::from(vec![Span::raw(format!("  {}", self.channel.username))]),
         ]
@@ -138,42 +270,27 @@ impl Video {
         }
     }
 
-    async fn unsubscribe(&mut self) {
-        let pool = crate::config::db::get();
-        let mut connection = pool.acquire().await.unwrap();
-
-        let result = sqlx::query!(
-            r#"
-                DELETE FROM subscriptions WHERE channel_id = ?1;"#,
-            self.channel.id,
-        )
-        .execute(&mut *connection)
-        .await;
-
-        let tag = match result {
-            Ok(e) if e.rows_affected() == 0 => {
-                String::from("You're not subscribed to this channel")
-            }
-            Ok(_) => String::from("Unsubscribed"),
-            Err(_) => String::from("Some error occur on unsubscribe"),
-        };
-
-        self.tag = tag;
-    }
-
-    async fn download(&mut self, download_type: DownloadType) {
-        let url = self.url.clone();
-
-        tokio::task::spawn(async move {
-            let _ = match download_from_yt(&url, download_type).await {
-                Ok(_) => String::from("Downloaded!"),
-                Err(_) => String::from("Some error occur on dowload!"),
-            };
+    fn download(&self, download_type: DownloadType, format: Option<String>) -> Result<(), String> {
+        downloads::get().enqueue(DownloadRequest {
+            url: self.url.clone(),
+            title: self.title.clone(),
+            channel: Some(self.channel.username.clone()),
+            download_type,
+            scope: DownloadScope::Single,
+            format,
+            match_filter: None,
+            rule_channel_id: None,
         });
+        Ok(())
     }
 
-    async fn play(&mut self) {
-        let _ = play_video_command(self.url.clone()).await;
+    async fn play(
+        &mut self,
+        mode: PlaybackMode,
+        format: Option<String>,
+        notify: impl Fn(String),
+    ) -> Result<(), String> {
+        player::play(self, mode, format, notify).await
     }
 }
 
@@ -190,7 +307,7 @@ impl Channel {
         Self {
             id: id.to_string(),
             username: username.to_string(),
-            url: format!("https://www.youtube.com/{}", id.to_string()),
+            url: format!("https://www.youtube.com/{}", id),
             tag: String::new(),
         }
     }
@@ -201,20 +318,52 @@ impl Channel {
         }
     }
 
-    fn display(&self, selected: bool) -> Vec<Line> {
+    /// Queues the uploads of this channel picked by `scope` as one download.
+    pub fn download(&self, download_type: DownloadType, scope: DownloadScope) {
+        let title = match &scope {
+            DownloadScope::Latest(count) => format!("{} (latest {})", self.username, count),
+            DownloadScope::DateRange(after, before) => {
+                format!("{} ({} to {})", self.username, after, before)
+            }
+            DownloadScope::Single | DownloadScope::All | DownloadScope::Section(_) => {
+                self.username.clone()
+            }
+        };
+
+        downloads::get().enqueue(DownloadRequest {
+            url: format!("{}/videos", self.url.trim_end_matches('/')),
+            title,
+            channel: Some(self.username.clone()),
+            download_type,
+            scope,
+            format: None,
+            match_filter: None,
+            rule_channel_id: None,
+        });
+    }
+
+    fn display(&self, selected: bool) -> Vec<Line<'_>> {
         if selected {
             return vec![Line::from(vec![
                 Span::styled(
                     format!("> {}\n", self.username),
-                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                    Style::default()
+                        .fg(config::get().ui.channel)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(
+                    format!(" {}\n", self.tag),
+                    Style::default().fg(config::get().ui.tag),
                 ),
-                Span::styled(format!(" {}\n", self.tag), Style::default().fg(Color::Blue)),
             ])];
         }
 
         vec![Line::from(vec![
             Span::raw(format!("  {}\n", self.username)),
-            Span::styled(format!(" {}\n", self.tag), Style::default().fg(Color::Blue)),
+            Span::styled(
+                format!(" {}\n", self.tag),
+                Style::default().fg(config::get().ui.tag),
+            ),
         ])]
     }
 }
@@ -235,7 +384,26 @@ pub enum PlaylistUploader {
 }
 
 impl Playlist {
-    fn display(&self, selected: bool) -> Vec<Line> {
+    fn download(&self, download_type: DownloadType, format: Option<String>) -> Result<(), String> {
+        let channel = match &self.uploader {
+            PlaylistUploader::Channel(channel) => Some(channel.username.clone()),
+            PlaylistUploader::MultiUploaders(_) => None,
+        };
+
+        downloads::get().enqueue(DownloadRequest {
+            url: self.url.clone(),
+            title: format!("{} (playlist)", self.title),
+            channel,
+            download_type,
+            scope: DownloadScope::All,
+            format,
+            match_filter: None,
+            rule_channel_id: None,
+        });
+        Ok(())
+    }
+
+    fn display(&self, selected: bool) -> Vec<Line<'_>> {
         let uploader_username = match &self.uploader {
             PlaylistUploader::MultiUploaders(username) => username.clone(),
             PlaylistUploader::Channel(channel) => channel.username.clone(),
@@ -247,15 +415,18 @@ impl Playlist {
                     Span::styled(
                         format!("> {}\n", self.title),
                         Style::default()
-                            .fg(Color::Green)
+                            .fg(config::get().ui.playlist)
                             .add_modifier(Modifier::BOLD),
                     ),
-                    Span::styled(format!(" {}\n", self.tag), Style::default().fg(Color::Blue)),
+                    Span::styled(
+                        format!(" {}\n", self.tag),
+                        Style::default().fg(config::get().ui.tag),
+                    ),
                 ]),
                 Line::from(vec![Span::styled(
                     format!("  {}", uploader_username),
                     Style::default()
-                        .fg(Color::Yellow)
+                        .fg(config::get().ui.highlight)
                         .add_modifier(Modifier::BOLD),
                 )]),
             ];
@@ -264,7 +435,10 @@ impl Playlist {
         vec![
             Line::from(vec![
                 Span::raw(format!("  {}\n", self.title)),
-                Span::styled(format!(" {}\n", self.tag), Style::default().fg(Color::Blue)),
+                Span::styled(
+                    format!(" {}\n", self.tag),
+                    Style::default().fg(config::get().ui.tag),
+                ),
             ]),
             Line::from(vec![Span::raw(format!("  {}", uploader_username))]),
         ]
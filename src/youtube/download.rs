@@ -1,37 +1,446 @@
-use std::{io, process::Command};
+use std::{fmt, path::Path, str::FromStr};
 
-use crate::config::{AUDIO_DOWNLOAD_PATH, VIDEO_DOWNLOAD_PATH};
+use chrono::NaiveDate;
+use tokio::process::Command;
 
+use super::normalize_url;
+use crate::config::{self, PlaybackMode, data_dir};
+
+/// Prefix of the progress lines yt-dlp prints through `PROGRESS_TEMPLATE`.
+pub const PROGRESS_PREFIX: &str = "[silk]";
+
+/// Downloaded bytes, total bytes, estimated total bytes, speed in bytes per second and ETA in
+/// seconds. Unknown values are printed as `NA`.
+const PROGRESS_TEMPLATE: &str = "download:[silk] %(progress.downloaded_bytes)s %(progress.total_bytes)s %(progress.total_bytes_estimate)s %(progress.speed)s %(progress.eta)s";
+
+/// Prefix of the line yt-dlp prints for each finished file through `FILE_TEMPLATE`.
+pub const FILE_PREFIX: &str = "[silk-file]\t";
+
+/// Video id, format id, uploader, title and final path of a finished file, tab separated.
+const FILE_TEMPLATE: &str =
+    "after_move:[silk-file]\t%(id)s\t%(format_id)s\t%(uploader)s\t%(title)s\t%(filepath)s";
+
+#[derive(Clone, Copy, PartialEq)]
 pub enum DownloadType {
     Video,
     Audio,
 }
 
-pub async fn download_from_yt(url: &str, download_type: DownloadType) -> Result<(), io::Error> {
-    let normalized_url = match url {
-        u if u.starts_with("http") => u.to_string(),
-        u if u.starts_with("/") => format!("https://www.youtube.com{}", url),
-        _ => format!("https://www.youtube.com/{}", url),
-    };
+impl DownloadType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DownloadType::Video => "video",
+            DownloadType::Audio => "audio",
+        }
+    }
+}
+
+impl FromStr for DownloadType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "video" => Ok(DownloadType::Video),
+            "audio" => Ok(DownloadType::Audio),
+            other => Err(format!("Unknown download type: {}", other)),
+        }
+    }
+}
+
+/// Codec audio downloads are converted to.
+#[derive(Clone, Copy, PartialEq)]
+pub enum AudioFormat {
+    Opus,
+    M4a,
+    Mp3,
+    Flac,
+}
+
+impl AudioFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AudioFormat::Opus => "opus",
+            AudioFormat::M4a => "m4a",
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Flac => "flac",
+        }
+    }
+}
+
+impl fmt::Display for AudioFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AudioFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "opus" => Ok(AudioFormat::Opus),
+            "m4a" => Ok(AudioFormat::M4a),
+            "mp3" => Ok(AudioFormat::Mp3),
+            "flac" => Ok(AudioFormat::Flac),
+            other => Err(format!(
+                "Unknown audio format: {} (expected opus, m4a, mp3 or flac)",
+                other
+            )),
+        }
+    }
+}
+
+/// Which videos behind a URL are downloaded, or which part of the video.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DownloadScope {
+    /// Only the video, even when the URL also points into a playlist.
+    Single,
+    /// Every video of the playlist or channel.
+    All,
+    /// The latest uploads of a channel.
+    Latest(usize),
+    /// Uploads between two dates, inclusive, as `YYYYMMDD`.
+    DateRange(String, String),
+    /// Part of a single video: a time range as `*start-end`, or a chapter title.
+    Section(String),
+}
+
+impl DownloadScope {
+    /// Parses what is typed when downloading from a channel: a number of latest uploads, or a
+    /// `YYYYMMDD-YYYYMMDD` date range.
+    pub fn parse_channel_input(input: &str) -> Result<Self, String> {
+        let input = input.trim();
+
+        if let Ok(count) = input.parse::<usize>() {
+            return match count {
+                0 => Err(String::from("Download at least one video")),
+                count => Ok(DownloadScope::Latest(count)),
+            };
+        }
+
+        let invalid = || format!("Expected a number or YYYYMMDD-YYYYMMDD, got \"{}\"", input);
+        let (after, before) = input.split_once('-').ok_or_else(invalid)?;
+        for date in [after, before] {
+            NaiveDate::parse_from_str(date, "%Y%m%d").map_err(|_| invalid())?;
+        }
+
+        Ok(DownloadScope::DateRange(
+            after.to_string(),
+            before.to_string(),
+        ))
+    }
+
+    /// Parses what is typed when clipping a video: a `start-end` time range such as `1:00-3:00`,
+    /// or the title of a chapter.
+    pub fn parse_section_input(input: &str) -> Result<Self, String> {
+        let input = input.trim();
+
+        if input.is_empty() {
+            return Err(String::from(
+                "Expected a start-end time range or a chapter title",
+            ));
+        }
+
+        let range = input
+            .split_once('-')
+            .and_then(|(start, end)| Some((parse_timestamp(start)?, parse_timestamp(end)?)));
 
-    let path = match download_type {
-        DownloadType::Video => VIDEO_DOWNLOAD_PATH,
-        DownloadType::Audio => AUDIO_DOWNLOAD_PATH,
-    };
+        match range {
+            Some((start, end)) if end <= start => {
+                Err(String::from("The clip must end after it starts"))
+            }
+            Some(_) => Ok(DownloadScope::Section(format!(
+                "*{}",
+                input.replace(' ', "")
+            ))),
+            None => Ok(DownloadScope::Section(input.to_string())),
+        }
+    }
+
+    /// Human readable form of a section, e.g. `1:00-3:00` or `chapter "Intro"`.
+    pub fn section_label(&self) -> Option<String> {
+        match self {
+            DownloadScope::Section(spec) => Some(match spec.strip_prefix('*') {
+                Some(range) => range.to_string(),
+                None => format!("chapter \"{}\"", spec),
+            }),
+            _ => None,
+        }
+    }
+
+    fn args(&self) -> Vec<String> {
+        let mut args = vec![String::from(match self {
+            DownloadScope::Single | DownloadScope::Section(_) => "--no-playlist",
+            _ => "--yes-playlist",
+        })];
+
+        match self {
+            DownloadScope::Latest(count) => {
+                args.extend([String::from("--playlist-items"), format!("1:{}", count)])
+            }
+            DownloadScope::DateRange(after, before) => args.extend([
+                String::from("--dateafter"),
+                after.clone(),
+                String::from("--datebefore"),
+                before.clone(),
+            ]),
+            DownloadScope::Section(spec) => args.extend([
+                String::from("--download-sections"),
+                match spec.starts_with('*') {
+                    true => spec.clone(),
+                    // yt-dlp reads chapters as a regex, the title has to match as written
+                    false => format!("^{}$", regex::escape(spec)),
+                },
+                String::from("--force-keyframes-at-cuts"),
+            ]),
+            DownloadScope::Single | DownloadScope::All => {}
+        }
+
+        args
+    }
+}
+
+/// Stored form: `single`, `all`, `latest:N`, `dates:YYYYMMDD-YYYYMMDD` or `section:SPEC`.
+impl fmt::Display for DownloadScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DownloadScope::Single => write!(f, "single"),
+            DownloadScope::All => write!(f, "all"),
+            DownloadScope::Latest(count) => write!(f, "latest:{}", count),
+            DownloadScope::DateRange(after, before) => write!(f, "dates:{}-{}", after, before),
+            DownloadScope::Section(spec) => write!(f, "section:{}", spec),
+        }
+    }
+}
+
+impl FromStr for DownloadScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(spec) = s.strip_prefix("section:") {
+            return Ok(DownloadScope::Section(spec.to_string()));
+        }
+
+        match s {
+            "single" => Ok(DownloadScope::Single),
+            "all" => Ok(DownloadScope::All),
+            s if s.starts_with("latest:") || s.starts_with("dates:") => {
+                DownloadScope::parse_channel_input(s.split_once(':').unwrap_or_default().1)
+            }
+            other => Err(format!("Unknown download scope: {}", other)),
+        }
+    }
+}
+
+/// Parses `h:mm:ss`, `m:ss` or a number of seconds.
+fn parse_timestamp(timestamp: &str) -> Option<f64> {
+    timestamp.trim().split(':').try_fold(0.0, |total, part| {
+        Some(total * 60.0 + part.parse::<f64>().ok()?)
+    })
+}
+
+/// File recording every downloaded video, so that bulk downloads skip what is already there.
+pub fn archive_path() -> std::path::PathBuf {
+    data_dir().join("download-archive.txt")
+}
+
+/// Converts a `{field}` file name template into a yt-dlp output template. Fields are yt-dlp's,
+/// plus `{channel}` for the uploader.
+pub fn output_template(template: &str) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        output.push_str(&rest[..start].replace('%', "%%"));
+
+        let field = match &rest[start + 1..start + end] {
+            "channel" => "uploader",
+            field => field,
+        };
+        output.push_str(&format!("%({})s", field));
+        rest = &rest[start + end + 1..];
+    }
+    output.push_str(&rest.replace('%', "%%"));
+
+    output
+}
+
+/// yt-dlp command downloading the videos of `scope` behind `url` into `directory`. Playlists and
+/// channels skip the videos recorded in the download archive. Sections bypass the archive and get their range in the file
+/// name, since they are not the whole video. `format` overrides the configured format preference; split
+/// video and audio streams are merged into one file. Title, artist (the channel), date and chapters
+/// are embedded, as is the thumbnail as cover art for audio. Progress is printed to stdout, one
+/// `PROGRESS_PREFIX` line per update, and every finished file is appended to `files_output` as a
+/// `FILE_PREFIX` line. Partial files left by an interrupted run are resumed.
+pub fn download_command(
+    url: &str,
+    download_type: DownloadType,
+    format: Option<String>,
+    directory: &Path,
+    scope: &DownloadScope,
+    match_filter: Option<&str>,
+    files_output: &Path,
+) -> Command {
+    let config = config::get();
+    let format = format.unwrap_or_else(|| match download_type {
+        DownloadType::Video => PlaybackMode::Video.format(),
+        DownloadType::Audio => PlaybackMode::Audio.format(),
+    });
+
+    let mut template = output_template(&config.downloads.output_template);
+    if let DownloadScope::Section(_) = scope {
+        template = match template.strip_suffix(".%(ext)s") {
+            Some(stem) => format!("{} (%(section_start)d-%(section_end)d).%(ext)s", stem),
+            None => format!("{} (%(section_start)d-%(section_end)d)", template),
+        };
+    }
 
     let mut cmd = Command::new("yt-dlp");
     cmd.arg("-P")
-        .arg(path)
+        .arg(directory)
+        .arg("-o")
+        .arg(template)
         .arg("-f")
-        .arg("best[ext=mp4]/best")
-        .arg("--no-playlist")
-        .arg(&normalized_url);
+        .arg(&format)
+        .arg("--merge-output-format")
+        .arg("mp4/mkv")
+        .args(scope.args())
+        .arg("--continue")
+        .arg("--newline")
+        .arg("--progress-template")
+        .arg(PROGRESS_TEMPLATE)
+        .arg("--embed-metadata")
+        .arg("--parse-metadata")
+        .arg("uploader:%(meta_artist)s")
+        .arg("--embed-chapters")
+        .arg("--print-to-file")
+        .arg(FILE_TEMPLATE)
+        .arg(files_output);
+
+    // Single videos and clips are downloaded whenever asked for, whatever the archive says
+    if !matches!(scope, DownloadScope::Single | DownloadScope::Section(_)) {
+        cmd.arg("--download-archive").arg(archive_path());
+    }
+
+    if let (DownloadType::Video, Some(langs)) = (download_type, &config.player.subtitle_langs) {
+        cmd.arg("--sub-langs").arg(langs);
+        if config.player.subtitle_source.manual() {
+            cmd.arg("--write-subs");
+        }
+        if config.player.subtitle_source.auto() {
+            cmd.arg("--write-auto-subs");
+        }
+        if config.downloads.embed_subtitles {
+            cmd.arg("--embed-subs");
+        }
+    }
+
+    if let Some(filter) = match_filter {
+        cmd.arg("--match-filters").arg(filter);
+    }
+
+    if !matches!(scope, DownloadScope::Single | DownloadScope::Section(_)) {
+        // Keep going past unavailable videos; yt-dlp still exits with an error at the end
+        cmd.arg("--ignore-errors");
+    }
 
     if let DownloadType::Audio = download_type {
-        cmd.arg("--extract-audio").arg("--audio-format").arg("mp3");
+        cmd.arg("--extract-audio")
+            .arg("--audio-format")
+            .arg(config.downloads.audio_format.as_str())
+            .arg("--audio-quality")
+            .arg(&config.downloads.audio_quality)
+            .arg("--embed-thumbnail")
+            .arg("--convert-thumbnails")
+            .arg("jpg");
     }
 
-    cmd.output()?;
+    cmd.arg("--").arg(normalize_url(url));
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    Ok(())
+    #[test]
+    fn output_template_maps_fields() {
+        assert_eq!(
+            output_template("{channel}/{upload_date} - {title}.{ext}"),
+            "%(uploader)s/%(upload_date)s - %(title)s.%(ext)s"
+        );
+    }
+
+    #[test]
+    fn output_template_escapes_literal_percent_signs() {
+        assert_eq!(output_template("100% {title}"), "100%% %(title)s");
+        assert_eq!(output_template("{title} {50%"), "%(title)s {50%%");
+    }
+
+    #[test]
+    fn channel_input_takes_a_count_or_a_date_range() {
+        assert_eq!(
+            DownloadScope::parse_channel_input(" 5 "),
+            Ok(DownloadScope::Latest(5))
+        );
+        assert_eq!(
+            DownloadScope::parse_channel_input("20240101-20241231"),
+            Ok(DownloadScope::DateRange(
+                String::from("20240101"),
+                String::from("20241231")
+            ))
+        );
+    }
+
+    #[test]
+    fn channel_input_rejects_bad_values() {
+        assert!(DownloadScope::parse_channel_input("0").is_err());
+        assert!(DownloadScope::parse_channel_input("latest").is_err());
+        assert!(DownloadScope::parse_channel_input("20241301-20241231").is_err());
+    }
+
+    #[test]
+    fn scopes_round_trip_through_their_stored_form() {
+        for scope in [
+            DownloadScope::Single,
+            DownloadScope::All,
+            DownloadScope::Latest(3),
+            DownloadScope::DateRange(String::from("20240101"), String::from("20240201")),
+            DownloadScope::Section(String::from("*1:00-2:00")),
+        ] {
+            assert_eq!(scope.to_string().parse(), Ok(scope));
+        }
+    }
+
+    #[test]
+    fn section_input_takes_a_time_range() {
+        let scope = DownloadScope::parse_section_input("1:00 - 1:02:03").unwrap();
+        assert_eq!(scope, DownloadScope::Section(String::from("*1:00-1:02:03")));
+        assert_eq!(scope.section_label().as_deref(), Some("1:00-1:02:03"));
+        assert!(scope.args().contains(&String::from("*1:00-1:02:03")));
+    }
+
+    #[test]
+    fn section_input_takes_a_chapter_title() {
+        let scope = DownloadScope::parse_section_input(" Intro (part 1) ").unwrap();
+        assert_eq!(
+            scope,
+            DownloadScope::Section(String::from("Intro (part 1)"))
+        );
+        assert_eq!(
+            scope.section_label().as_deref(),
+            Some("chapter \"Intro (part 1)\"")
+        );
+        assert!(scope.args().contains(&String::from(r"^Intro \(part 1\)$")));
+    }
+
+    #[test]
+    fn section_input_rejects_empty_and_backwards_ranges() {
+        assert!(DownloadScope::parse_section_input("  ").is_err());
+        assert!(DownloadScope::parse_section_input("3:00-1:00").is_err());
+        assert!(DownloadScope::parse_section_input("1:00-1:00").is_err());
+    }
 }
@@ -0,0 +1,101 @@
+use serde_json::Value;
+use tokio::process::Command;
+
+use super::normalize_url;
+
+#[derive(Clone, PartialEq)]
+pub struct Format {
+    pub id: String,
+    pub ext: String,
+    pub resolution: String,
+    pub vcodec: Option<String>,
+    pub acodec: Option<String>,
+    pub fps: Option<f64>,
+    pub tbr: Option<f64>,
+    pub filesize: Option<u64>,
+}
+
+impl Format {
+    fn from_json(format: &Value) -> Option<Self> {
+        let codec = |key: &str| {
+            format[key]
+                .as_str()
+                .filter(|codec| *codec != "none")
+                .map(String::from)
+        };
+
+        Some(Self {
+            id: format["format_id"].as_str()?.to_string(),
+            ext: format["ext"].as_str().unwrap_or_default().to_string(),
+            resolution: format["resolution"]
+                .as_str()
+                .unwrap_or("audio only")
+                .to_string(),
+            vcodec: codec("vcodec"),
+            acodec: codec("acodec"),
+            fps: format["fps"].as_f64(),
+            tbr: format["tbr"].as_f64(),
+            filesize: format["filesize"]
+                .as_u64()
+                .or(format["filesize_approx"].as_u64()),
+        })
+    }
+
+    /// yt-dlp format selector for this format. Video-only streams are paired with the best audio
+    /// so that the player or downloader merges them.
+    pub fn selector(&self) -> String {
+        match (&self.vcodec, &self.acodec) {
+            (Some(_), None) => format!("{}+bestaudio", self.id),
+            _ => self.id.clone(),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let codecs = match (&self.vcodec, &self.acodec) {
+            (Some(v), Some(a)) => format!("{v} + {a}"),
+            (Some(v), None) => format!("{v} (video only)"),
+            (None, Some(a)) => format!("{a} (audio only)"),
+            (None, None) => String::from("unknown"),
+        };
+        let fps = self.fps.map(|f| format!("{f}fps")).unwrap_or_default();
+        let bitrate = self.tbr.map(|t| format!("{t:.0}k")).unwrap_or_default();
+        let size = self
+            .filesize
+            .map(|s| format!("{:.1}MiB", s as f64 / (1024.0 * 1024.0)))
+            .unwrap_or_default();
+
+        format!(
+            "{:<6} {:<5} {:<11} {:<6} {:<7} {:<10} {}",
+            self.id, self.ext, self.resolution, fps, bitrate, size, codecs
+        )
+    }
+}
+
+/// Lists the formats yt-dlp can fetch for `url`, best last, as yt-dlp orders them.
+pub async fn list_formats(url: &str) -> Result<Vec<Format>, String> {
+    let output = Command::new("yt-dlp")
+        .args(["-J", "--no-playlist", &normalize_url(url)])
+        .output()
+        .await
+        .map_err(|_| String::from("Failed to run yt-dlp"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "yt-dlp failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let json: Value = serde_json::from_slice(&output.stdout)
+        .map_err(|_| String::from("Failed to parse yt-dlp output"))?;
+
+    let formats = json["formats"]
+        .as_array()
+        .ok_or(String::from("No formats found"))?
+        .iter()
+        .filter_map(Format::from_json)
+        .filter(|f| f.vcodec.is_some() || f.acodec.is_some())
+        .collect();
+
+    Ok(formats)
+}
@@ -1,69 +1,151 @@
-use std::{error::Error, io::Stdout, process::Command, thread::sleep, time::Duration};
+use std::{cell::RefCell, error::Error, io::Stdout};
 
 use crate::{
-    config::play_video_command,
+    config::{self, PlaybackMode},
+    player, rules,
     types::{Channel, ChannelDB, ContentItem, Video, VideoDB},
     youtube::parser::parse_contents,
 };
-use chrono::{TimeDelta, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
 use parser::parse_channel_videos;
-use ratatui::{prelude::CrosstermBackend, text::Span};
+use ratatui::{
+    prelude::CrosstermBackend,
+    text::{Line, Text},
+};
 use regex::Regex;
 use serde_json::Value;
 
 pub mod download;
+pub mod formats;
 pub mod parser;
+pub mod resolver;
 
 type Terminal = ratatui::Terminal<CrosstermBackend<Stdout>>;
 
-pub async fn play_video(terminal: &mut Terminal, url: &str) -> Result<(), Box<dyn Error>> {
-    terminal.clear()?;
-    terminal.draw(|f| f.render_widget(Span::raw(" Video Loading..."), f.area()))?;
-    terminal.hide_cursor()?;
-
-    let normalized_url = match url {
+/// Turns the relative paths found in scraped pages into full YouTube URLs.
+pub fn normalize_url(url: &str) -> String {
+    match url {
         u if u.starts_with("http") => u.to_string(),
         u if u.starts_with("/") => format!("https://www.youtube.com{}", url),
         _ => format!("https://www.youtube.com/{}", url),
-    };
-    let output = Command::new("yt-dlp")
-        .args(["-f", "best[ext=mp4]/best", "-g", &normalized_url])
-        .output()?;
-
-    if !output.status.success() {
-        eprintln!("yt-dlp failed: {}", String::from_utf8_lossy(&output.stderr));
-        return Ok(());
     }
+}
 
-    let stream_url = String::from_utf8_lossy(&output.stdout).trim().to_string();
+/// Plays the video at `url` like the TUI does: from the library when downloaded, resuming
+/// where it was left and skipping SponsorBlock segments.
+pub async fn play_video(
+    terminal: &mut Terminal,
+    url: &str,
+    mode: PlaybackMode,
+) -> Result<(), Box<dyn Error>> {
+    let video = video_for_url(url).await?;
+
+    let loading = match mode {
+        PlaybackMode::Video => " Video Loading...",
+        PlaybackMode::Audio => " Audio Loading...",
+    };
+    // Stays on screen while the player runs, since it does not write to the terminal
+    let terminal = RefCell::new(terminal);
+    let show = |notice: Option<String>| {
+        let mut lines = vec![Line::raw(loading)];
+        if let Some(notice) = notice {
+            log::info!("{}", notice);
+            lines.push(Line::raw(format!(" {}", notice)));
+        }
+        let mut terminal = terminal.borrow_mut();
+        terminal.clear()?;
+        terminal.draw(|f| f.render_widget(Text::from(lines), f.area()))?;
+        terminal.hide_cursor()
+    };
+    show(None)?;
 
-    let _ = play_video_command(stream_url).await;
+    player::play_interactive(&video, mode, |notice| {
+        let _ = show(Some(notice));
+    })
+    .await?;
 
-    sleep(Duration::from_secs(3));
-    terminal.autoresize()?;
+    terminal.borrow_mut().clear()?;
 
     Ok(())
 }
 
+/// The video a URL or bare id points at, named as the feed or history knows it.
+async fn video_for_url(url: &str) -> Result<Video, String> {
+    let id = video_id(url).ok_or_else(|| format!("Not a YouTube video: {}", url))?;
+    let pool = crate::config::db::get();
+
+    let known = sqlx::query!(
+        r#"
+            SELECT feed.title AS "title!", subscriptions.channel_id AS "channel_id!",
+            subscriptions.channel_username AS "channel_username!"
+            FROM feed
+            JOIN subscriptions ON feed.channel = subscriptions.channel_id
+            WHERE feed.id = ?1
+            UNION ALL
+            SELECT title, channel_id, channel_username FROM history WHERE video_id = ?1
+            LIMIT 1;
+        "#,
+        id
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(|_| String::from("Failed to look up the video"))?;
+
+    let (title, channel) = match known {
+        Some(row) => (
+            row.title,
+            Channel::new(&row.channel_id, &row.channel_username),
+        ),
+        None => (url.to_string(), Channel::new("", "")),
+    };
+
+    Ok(Video {
+        url: format!("https://www.youtube.com/watch?v={}", id),
+        id,
+        title,
+        tag: String::new(),
+        channel,
+        published_at: Utc::now(),
+        watched: false,
+        description: String::new(),
+    })
+}
+
+/// The id in a watch, short, embed or youtu.be URL, or `input` itself when it is a bare id.
+pub fn video_id(input: &str) -> Option<String> {
+    let re = Regex::new(
+        r"^[A-Za-z0-9_-]{11}$|(?:[?&]v=|youtu\.be/|/shorts/|/embed/|/live/)([A-Za-z0-9_-]{11})",
+    )
+    .expect("valid regex");
+    let captures = re.captures(input.trim())?;
+    let id = captures.get(1).or_else(|| captures.get(0))?;
+    Some(id.as_str().to_string())
+}
+
 pub async fn search_content(query: &str) -> Result<Vec<ContentItem>, String> {
     let url = format!("https://www.youtube.com/results?search_query={}", query);
 
     let json = fetch_youtube_content(&url).await?;
 
+    let result = parse_search_results(&json)?.into_iter().take(10).collect();
+
+    Ok(result)
+}
+
+/// Items of the `ytInitialData` of a search results page.
+pub fn parse_search_results(json: &Value) -> Result<Vec<ContentItem>, String> {
     let json = json["contents"]["twoColumnSearchResultsRenderer"]
         ["primaryContents"]["sectionListRenderer"]["contents"][0]
         ["itemSectionRenderer"]["contents"]
         .as_array()
         .ok_or(String::from("Content not found")).cloned()?;
 
-    let result = parse_contents(json).into_iter().take(10).collect();
-
-    Ok(result)
+    Ok(parse_contents(json))
 }
 
 pub async fn fetch_youtube_content(url: &str) -> Result<Value, String> {
     let client = reqwest::Client::builder()
-        .user_agent("Mozilla/5.0")
+        .user_agent(&config::get().network.user_agent)
         .build()
         .unwrap();
 
@@ -81,59 +163,166 @@ pub async fn fetch_youtube_content(url: &str) -> Result<Value, String> {
 
     let caps = re.captures(&res).ok_or("ytInitialData not found")?;
 
-    Ok(serde_json::from_str(&caps[1]).map_err(|_| String::from("Failed to parse html"))?)
+    serde_json::from_str(&caps[1]).map_err(|_| String::from("Failed to parse html"))
 }
 
-pub async fn update_feed() {
+/// Fetches the recent uploads of every subscription into the feed, refreshing the videos already
+/// in it, queues the downloads their rules ask for and prunes what is past the feed retention.
+pub async fn update_feed() -> Result<(), String> {
     let pool = crate::config::db::get();
 
-    let subscribed_channels = sqlx::query_as!(ChannelDB, r#"SELECT * FROM subscriptions"#)
-        .fetch_all(&pool)
-        .await
-        .unwrap();
+    let subscribed_channels = sqlx::query_as!(
+        ChannelDB,
+        r#"SELECT channel_id, channel_username FROM subscriptions"#
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|_| String::from("Failed to read subscriptions"))?;
 
-    let subscribed_channels = subscribed_channels
-        .into_iter()
-        .map(|e| Channel {
-            id: e.channel_id.clone(),
-            username: e.channel_username,
-            url: format!("https://www.youtube.com/{}", e.channel_id),
-            tag: String::new(),
-        })
-        .collect::<Vec<Channel>>();
+    refresh_channels(subscribed_channels).await.map(|_| ())
+}
+
+/// Updates the feed with the recent uploads of one subscription only.
+pub async fn refresh_subscription(channel_id: &str) -> Result<(), String> {
+    let pool = crate::config::db::get();
+
+    let channel = sqlx::query_as!(
+        ChannelDB,
+        r#"SELECT channel_id, channel_username FROM subscriptions WHERE channel_id = ?1"#,
+        channel_id
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(|_| String::from("Failed to read subscriptions"))?
+    .ok_or(String::from("You're not subscribed to this channel"))?;
+
+    match refresh_channels(vec![channel]).await?.pop() {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Does the work of `update_feed` for `channels`, recording how each refresh went. Channels that
+/// cannot be fetched are skipped; their errors are returned.
+async fn refresh_channels(channels: Vec<ChannelDB>) -> Result<Vec<String>, String> {
+    let pool = crate::config::db::get();
+    let feed = &config::get().feed;
 
     let mut feed_videos: Vec<Video> = Vec::new();
-    for e in subscribed_channels.into_iter() {
-        feed_videos.append(&mut parse_channel_videos(e).await.unwrap());
+    let mut outcomes = Vec::new();
+    for e in channels.into_iter() {
+        let channel = Channel::new(&e.channel_id, &e.channel_username);
+        match parse_channel_videos(channel).await {
+            Ok(mut videos) => {
+                feed_videos.append(&mut videos);
+                outcomes.push((e.channel_id, None));
+            }
+            Err(error) => {
+                log::warn!("{}: {}", e.channel_username, error);
+                outcomes.push((e.channel_id, Some(error)));
+            }
+        }
     }
 
     let mut feed_videos = feed_videos
         .into_iter()
-        .filter(|e| e.published_at >= Utc::now() - TimeDelta::days(7))
+        .filter(|e| e.published_at >= Utc::now() - TimeDelta::days(feed.days))
         .collect::<Vec<Video>>();
 
-    feed_videos.sort_by(|a, b| a.published_at.cmp(&b.published_at));
+    feed_videos.sort_by_key(|a| a.published_at);
 
-    let mut connection = pool.acquire().await.unwrap();
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|_| String::from("Failed to update the feed"))?;
+    let mut new_videos = Vec::new();
 
     for e in feed_videos {
-        let published_at = e.published_at.to_string();
-
-        let _ = sqlx::query!(
-            r#" INSERT INTO feed ( id, title, url, channel, published_at ) VALUES ( ?1, ?2, ?3, ?4, ?5 ) "#,
+        let published_at = e.published_at.to_rfc3339();
+
+        let known = sqlx::query_scalar!(r#"SELECT EXISTS(SELECT 1 FROM feed WHERE id = ?1)"#, e.id)
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(|_| String::from("Failed to read the feed"))?;
+
+        // The publication date is parsed from "3 days ago", so the first one seen is kept
+        sqlx::query!(
+            r#"
+                INSERT INTO feed ( id, title, url, channel, published_at, description )
+                VALUES ( ?1, ?2, ?3, ?4, ?5, ?6 )
+                ON CONFLICT(id) DO UPDATE SET
+                    title = excluded.title,
+                    url = excluded.url,
+                    channel = excluded.channel,
+                    description = excluded.description
+            "#,
             e.id,
             e.title,
             e.url,
             e.channel.id,
-            published_at
+            published_at,
+            e.description
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| String::from("Failed to update the feed"))?;
+
+        if known == 0 {
+            new_videos.push(e);
+        }
+    }
+
+    let refreshed_at = Utc::now().to_rfc3339();
+    for (channel_id, error) in &outcomes {
+        sqlx::query!(
+            r#"UPDATE subscriptions SET refreshed_at = ?1, refresh_error = ?2 WHERE channel_id = ?3"#,
+            refreshed_at,
+            error,
+            channel_id
         )
-        .execute(&mut *connection)
-        .await;
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| String::from("Failed to update subscriptions"))?;
     }
+
+    if feed.retention_days > 0 {
+        let cutoff = (Utc::now() - TimeDelta::days(feed.retention_days)).to_rfc3339();
+        sqlx::query!(r#"DELETE FROM feed WHERE published_at < ?1"#, cutoff)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|_| String::from("Failed to prune the feed"))?;
+    }
+
+    transaction
+        .commit()
+        .await
+        .map_err(|_| String::from("Failed to update the feed"))?;
+
+    if let Err(e) = rules::queue_matching(&new_videos).await {
+        log::error!("{}", e);
+    }
+    if let Err(e) = rules::apply_retention().await {
+        log::error!("{}", e);
+    }
+
+    Ok(outcomes
+        .into_iter()
+        .filter_map(|(_, error)| error)
+        .collect())
+}
+
+/// How the Feed view filters and orders videos by watched state, and which group it shows.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct FeedFilter {
+    pub hide_watched: bool,
+    pub unwatched_first: bool,
+    /// Id of the group whose videos are shown, all subscriptions when `None`.
+    pub group: Option<i64>,
 }
 
-pub async fn get_feed_videos() -> Result<Vec<ContentItem>, String> {
+pub async fn get_feed_videos(filter: FeedFilter) -> Result<Vec<ContentItem>, String> {
     let pool = crate::config::db::get();
+    let limit = config::get().feed.limit;
 
     let feed_videos = sqlx::query_as!(
         VideoDB,
@@ -143,34 +332,98 @@ pub async fn get_feed_videos() -> Result<Vec<ContentItem>, String> {
             feed.title,
             feed.url,
             feed.published_at,
+            feed.watched,
+            feed.description,
             subscriptions.channel_id,
             subscriptions.channel_username
             FROM feed
             JOIN subscriptions ON feed.channel = subscriptions.channel_id
-            ORDER BY feed.published_at DESC
-            LIMIT 10;
-        "#
+            WHERE (?1 = FALSE OR feed.watched = FALSE)
+            AND (?4 IS NULL OR feed.channel IN (
+                SELECT channel_id FROM subscription_group_members WHERE group_id = ?4
+            ))
+            ORDER BY CASE WHEN ?2 THEN feed.watched ELSE FALSE END, feed.published_at DESC
+            LIMIT ?3;
+        "#,
+        filter.hide_watched,
+        filter.unwatched_first,
+        limit,
+        filter.group
     )
     .fetch_all(&pool)
     .await
-    .unwrap();
+    .map_err(|_| String::from("Failed to read the feed"))?;
 
+    // A row with a broken date is left out rather than failing the whole feed
     let feed_videos = feed_videos
         .into_iter()
-        .map(|e| Video {
-            id: e.id,
-            title: e.title,
-            channel: Channel::new(&e.channel_id, &e.channel_username),
-            published_at: e.published_at.parse().unwrap(),
-            url: format!("https://www.youtube.com/{}", e.url),
-            tag: String::new(),
+        .filter_map(|e| {
+            let published_at = match DateTime::parse_from_rfc3339(&e.published_at) {
+                Ok(published_at) => published_at,
+                Err(_) => {
+                    log::warn!(
+                        "Skipping {} in the feed: invalid publication date {:?}",
+                        e.id,
+                        e.published_at
+                    );
+                    return None;
+                }
+            };
+
+            Some(ContentItem::Video(Video {
+                channel: Channel::new(&e.channel_id, &e.channel_username),
+                published_at: published_at.with_timezone(&Utc),
+                url: normalize_url(&e.url),
+                id: e.id,
+                title: e.title,
+                tag: String::new(),
+                watched: e.watched,
+                description: e.description,
+            }))
         })
-        .map(|video| ContentItem::Video(video))
         .collect::<Vec<ContentItem>>();
 
     Ok(feed_videos)
 }
 
+pub async fn set_watched(video_id: &str, watched: bool) -> Result<(), String> {
+    let pool = crate::config::db::get();
+
+    sqlx::query!(
+        r#"UPDATE feed SET watched = ?2 WHERE id = ?1"#,
+        video_id,
+        watched
+    )
+    .execute(&pool)
+    .await
+    .map_err(|_| String::from("Failed to update watched state"))?;
+
+    Ok(())
+}
+
+/// Marks every feed video as watched, or only those of `channel_id` and of the channels in
+/// `group` when given.
+pub async fn mark_all_watched(channel_id: Option<&str>, group: Option<i64>) -> Result<(), String> {
+    let pool = crate::config::db::get();
+
+    sqlx::query!(
+        r#"
+            UPDATE feed SET watched = TRUE
+            WHERE (?1 IS NULL OR channel = ?1)
+            AND (?2 IS NULL OR channel IN (
+                SELECT channel_id FROM subscription_group_members WHERE group_id = ?2
+            ))
+        "#,
+        channel_id,
+        group
+    )
+    .execute(&pool)
+    .await
+    .map_err(|_| String::from("Failed to update watched state"))?;
+
+    Ok(())
+}
+
 pub async fn subscribe_to_channel(
     channel_id: &str,
     channel_username: &str,
@@ -200,3 +453,193 @@ pub async fn subscribe_to_channel(
 
     Ok(tag)
 }
+
+/// What unsubscribing from a channel removed, kept to undo it.
+#[derive(Clone, PartialEq)]
+pub struct Unsubscribed {
+    pub channel: ChannelDB,
+    /// When the feed of the channel was last refreshed, and why that failed if it did.
+    refreshed_at: Option<String>,
+    refresh_error: Option<String>,
+    feed: Vec<VideoDB>,
+    rule: Option<rules::Rule>,
+    /// Ids of the groups the channel was in.
+    groups: Vec<i64>,
+}
+
+/// Unsubscribes from `channel_id`. Its feed videos, auto-download rule and group memberships go
+/// with it.
+pub async fn unsubscribe(channel_id: &str) -> Result<Unsubscribed, String> {
+    let pool = crate::config::db::get();
+    let rule = rules::get_rules()
+        .await?
+        .into_iter()
+        .find(|rule| rule.channel_id == channel_id);
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|_| String::from("Failed to unsubscribe"))?;
+
+    let subscription = sqlx::query!(
+        r#"
+            SELECT channel_id, channel_username, refreshed_at, refresh_error
+            FROM subscriptions WHERE channel_id = ?1
+        "#,
+        channel_id
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(|_| String::from("Failed to read subscriptions"))?
+    .ok_or(String::from("You're not subscribed to this channel"))?;
+
+    let feed = sqlx::query_as!(
+        VideoDB,
+        r#"
+            SELECT
+            feed.id,
+            feed.title,
+            feed.url,
+            feed.published_at,
+            feed.watched,
+            feed.description,
+            subscriptions.channel_id,
+            subscriptions.channel_username
+            FROM feed
+            JOIN subscriptions ON feed.channel = subscriptions.channel_id
+            WHERE feed.channel = ?1
+        "#,
+        channel_id
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .map_err(|_| String::from("Failed to read the feed"))?;
+
+    let groups = sqlx::query_scalar!(
+        r#"SELECT group_id FROM subscription_group_members WHERE channel_id = ?1"#,
+        channel_id
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .map_err(|_| String::from("Failed to read groups"))?;
+
+    sqlx::query!(
+        r#"DELETE FROM subscriptions WHERE channel_id = ?1"#,
+        channel_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|_| String::from("Failed to unsubscribe"))?;
+
+    transaction
+        .commit()
+        .await
+        .map_err(|_| String::from("Failed to unsubscribe"))?;
+
+    Ok(Unsubscribed {
+        channel: ChannelDB {
+            channel_id: subscription.channel_id,
+            channel_username: subscription.channel_username,
+        },
+        refreshed_at: subscription.refreshed_at,
+        refresh_error: subscription.refresh_error,
+        feed,
+        rule,
+        groups,
+    })
+}
+
+impl Unsubscribed {
+    /// Subscribes again, bringing back the refresh status, the feed videos with their watched
+    /// state, the rule and the memberships of the groups that still exist.
+    pub async fn restore(&self) -> Result<(), String> {
+        let pool = crate::config::db::get();
+        let failed = |_| String::from("Failed to restore the subscription");
+
+        let mut transaction = pool.begin().await.map_err(failed)?;
+
+        sqlx::query!(
+            r#"
+                INSERT INTO subscriptions ( channel_id, channel_username, refreshed_at, refresh_error )
+                VALUES ( ?1, ?2, ?3, ?4 )
+                ON CONFLICT(channel_id) DO NOTHING
+            "#,
+            self.channel.channel_id,
+            self.channel.channel_username,
+            self.refreshed_at,
+            self.refresh_error
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(failed)?;
+
+        for video in &self.feed {
+            sqlx::query!(
+                r#"
+                    INSERT INTO feed ( id, title, url, channel, published_at, watched, description )
+                    VALUES ( ?1, ?2, ?3, ?4, ?5, ?6, ?7 )
+                    ON CONFLICT(id) DO NOTHING
+                "#,
+                video.id,
+                video.title,
+                video.url,
+                video.channel_id,
+                video.published_at,
+                video.watched,
+                video.description
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(failed)?;
+        }
+
+        for group_id in &self.groups {
+            sqlx::query!(
+                r#"
+                    INSERT OR IGNORE INTO subscription_group_members ( group_id, channel_id )
+                    SELECT id, ?2 FROM subscription_groups WHERE id = ?1
+                "#,
+                group_id,
+                self.channel.channel_id
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(failed)?;
+        }
+
+        transaction.commit().await.map_err(failed)?;
+
+        if let Some(rule) = &self.rule {
+            rules::set_rule(rule).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn video_id_reads_every_url_form() {
+        for url in [
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://www.youtube.com/watch?feature=share&v=dQw4w9WgXcQ&t=3",
+            "https://youtu.be/dQw4w9WgXcQ?t=3",
+            "https://www.youtube.com/shorts/dQw4w9WgXcQ",
+            "https://www.youtube.com/embed/dQw4w9WgXcQ",
+            "https://www.youtube.com/live/dQw4w9WgXcQ",
+            " dQw4w9WgXcQ ",
+        ] {
+            assert_eq!(video_id(url).as_deref(), Some("dQw4w9WgXcQ"), "{}", url);
+        }
+    }
+
+    #[test]
+    fn video_id_rejects_other_urls() {
+        assert_eq!(video_id("https://www.youtube.com/@channel"), None);
+        assert_eq!(video_id("https://example.com/video"), None);
+        assert_eq!(video_id("short"), None);
+    }
+}
@@ -40,11 +40,12 @@ pub async fn parse_channel_videos(channel: Channel) -> Result<Vec<Video>, String
         ["richGridRenderer"]["contents"]
         .as_array()
         .ok_or(format!("Error on parse {} channel videos.", &channel.id))?
-        .into_iter()
+        .iter()
         .flat_map(|e| {
             let id = remove_quotes(e["richItemRenderer"]["content"]["videoRenderer"]["videoId"].to_string());
             let title = remove_quotes(e["richItemRenderer"]["content"]["videoRenderer"]["title"]["runs"][0]["text"].to_string());
             let published_at = parse_time_published(&remove_quotes(e["richItemRenderer"]["content"]["videoRenderer"]["publishedTimeText"]["simpleText"].to_string()));
+            let description = parse_description(&e["richItemRenderer"]["content"]["videoRenderer"]);
 
             published_at.map(|published_at| Video {
                 id: id.clone(),
@@ -52,13 +53,24 @@ pub async fn parse_channel_videos(channel: Channel) -> Result<Vec<Video>, String
                 channel: channel.clone(),
                 url: format!("https://www.youtube.com/watch?v={id}"),
                 published_at,
-                tag: String::new()
+                tag: String::new(),
+                watched: false,
+                description,
             })
         }).collect::<Vec<Video>>();
 
     Ok(result)
 }
 
+/// Text of the description snippet of a video renderer, which comes in runs of differently
+/// formatted text.
+fn parse_description(renderer: &Value) -> String {
+    renderer["descriptionSnippet"]["runs"]
+        .as_array()
+        .map(|runs| runs.iter().filter_map(|run| run["text"].as_str()).collect())
+        .unwrap_or_default()
+}
+
 pub fn parse_contents(contents: Vec<Value>) -> Vec<ContentItem> {
     contents
         .iter()
@@ -118,6 +130,8 @@ pub fn parse_video_props(renderer: Value) -> Video {
         },
         published_at: Utc::now(), // TODO: get the published_at on search video
         tag: String::new(),
+        watched: false,
+        description: parse_description(&renderer),
     }
 }
 
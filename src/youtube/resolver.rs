@@ -0,0 +1,167 @@
+//! ## Resolver
+//!
+//! Turns a video page URL into stream URLs a player can open. yt-dlp is tried first; when it is
+//! missing or fails, the in-process rustube library takes over.
+
+use std::future::Future;
+
+use tokio::process::Command;
+
+use super::normalize_url;
+use crate::config::PlaybackMode;
+
+/// Stream URLs of a video, and how they were found.
+pub struct ResolvedStream {
+    pub url: String,
+    /// Separate audio stream to play along with `url`.
+    pub audio_url: Option<String>,
+    /// Name of the resolver that found the streams.
+    pub resolver: &'static str,
+    /// Why the primary resolver could not be used, when a fallback was.
+    pub fallback_reason: Option<String>,
+}
+
+impl ResolvedStream {
+    /// Message telling which resolver was used, when it was not the primary one.
+    pub fn notice(&self) -> Option<String> {
+        self.fallback_reason
+            .as_ref()
+            .map(|reason| format!("{}; streaming with {}", reason, self.resolver))
+    }
+}
+
+pub trait StreamResolver {
+    fn name(&self) -> &'static str;
+
+    /// Resolves `url` to a stream, plus a separate audio stream when the picked streams need
+    /// merging. `format` is a yt-dlp format selector; resolvers that cannot read one go by `mode`.
+    fn resolve(
+        &self,
+        url: &str,
+        format: &str,
+        mode: PlaybackMode,
+    ) -> impl Future<Output = Result<(String, Option<String>), String>> + Send;
+}
+
+pub struct YtDlp;
+
+impl StreamResolver for YtDlp {
+    fn name(&self) -> &'static str {
+        "yt-dlp"
+    }
+
+    async fn resolve(
+        &self,
+        url: &str,
+        format: &str,
+        _mode: PlaybackMode,
+    ) -> Result<(String, Option<String>), String> {
+        let output = Command::new("yt-dlp")
+            .args(["-f", format, "-g", "--no-playlist", &normalize_url(url)])
+            .output()
+            .await
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => String::from("yt-dlp is not installed"),
+                _ => format!("Failed to run yt-dlp: {}", e),
+            })?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!(
+                "yt-dlp failed: {}",
+                stderr.lines().last().unwrap_or_default().trim()
+            ));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut stream_urls = stdout.lines().map(|line| line.trim().to_string());
+        let stream_url = stream_urls
+            .next()
+            .ok_or(String::from("yt-dlp returned no stream"))?;
+
+        Ok((stream_url, stream_urls.next()))
+    }
+}
+
+/// Resolver built on rustube. It cannot read format selectors, so it takes the best video and
+/// audio streams, or the best audio stream alone.
+pub struct Native;
+
+impl StreamResolver for Native {
+    fn name(&self) -> &'static str {
+        "rustube"
+    }
+
+    async fn resolve(
+        &self,
+        url: &str,
+        _format: &str,
+        mode: PlaybackMode,
+    ) -> Result<(String, Option<String>), String> {
+        let video = fetch_video(url).await?;
+        let stream_url = |stream: &rustube::Stream| stream.signature_cipher.url.to_string();
+
+        let audio = video.best_audio().map(stream_url);
+        match mode {
+            PlaybackMode::Audio => audio
+                .or(video.best_quality().map(stream_url))
+                .map(|audio| (audio, None)),
+            PlaybackMode::Video => match (video.best_video(), audio) {
+                (Some(video), Some(audio)) => Some((stream_url(video), Some(audio))),
+                _ => video
+                    .best_quality()
+                    .map(|stream| (stream_url(stream), None)),
+            },
+        }
+        .ok_or(String::from("rustube found no playable stream"))
+    }
+}
+
+/// Fetches the video behind `url` with rustube.
+pub async fn fetch_video(url: &str) -> Result<rustube::Video, String> {
+    let url = rustube::url::Url::parse(&normalize_url(url))
+        .map_err(|e| format!("Invalid video URL: {}", e))?;
+
+    rustube::Video::from_url(&url)
+        .await
+        .map_err(|e| format!("rustube failed: {}", e))
+}
+
+/// Whether yt-dlp can be run at all.
+pub async fn yt_dlp_available() -> bool {
+    Command::new("yt-dlp")
+        .arg("--version")
+        .output()
+        .await
+        .is_ok_and(|output| output.status.success())
+}
+
+/// Resolves `url` with yt-dlp, falling back to rustube when yt-dlp fails.
+pub async fn resolve(
+    url: &str,
+    format: &str,
+    mode: PlaybackMode,
+) -> Result<ResolvedStream, String> {
+    let primary = match YtDlp.resolve(url, format, mode).await {
+        Ok((url, audio_url)) => {
+            return Ok(ResolvedStream {
+                url,
+                audio_url,
+                resolver: YtDlp.name(),
+                fallback_reason: None,
+            });
+        }
+        Err(e) => e,
+    };
+
+    log::warn!("{}, falling back to {}", primary, Native.name());
+    match Native.resolve(url, format, mode).await {
+        Ok((url, audio_url)) => Ok(ResolvedStream {
+            url,
+            audio_url,
+            resolver: Native.name(),
+            fallback_reason: Some(primary),
+        }),
+        Err(fallback) => Err(format!("{}; {}", primary, fallback)),
+    }
+}